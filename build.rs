@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use image::RgbaImage;
 use quote::__private::TokenStream;
 use quote::{format_ident, quote};
 
@@ -9,18 +11,211 @@ fn main() {
     // Generate asset structs based on the files in assets/ dir
     println!("cargo:rerun-if-changed=assets");
 
+    let problems = validate(Path::new("assets"));
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("error: {problem}");
+        }
+        eprintln!(
+            "\n{} problem(s) in assets/. Every file there has to be a PNG, and file and \
+             directory names become Rust names, so they have to be lowercase letters, digits \
+             and underscores, starting with a letter.",
+            problems.len()
+        );
+        std::process::exit(1);
+    }
+
     let out_dir = std::env::var_os("OUT_DIR").unwrap();
     let dest_path = std::path::Path::new(&out_dir).join("assets.rs");
     let mut assets_file = File::create(dest_path).expect("error creating generated file");
 
-    let (_, assets_struct) = visit_dir(Path::new("assets"));
+    let atlas = pack_atlas(Path::new("assets"));
+    atlas
+        .image
+        .save(Path::new(&out_dir).join("atlas.png"))
+        .expect("error writing atlas.png");
+    let (_, assets_struct) = visit_dir(Path::new("assets"), &atlas.rects, true);
 
     // println!("cargo:warning={:#}", assets_struct.to_string());
 
     assets_file.write_all(b"// @generated\n\n").unwrap();
+    writeln!(
+        assets_file,
+        "/// Hash of every file in assets/, to tell builds with different assets apart\n\
+         pub const MANIFEST_HASH: &str = \"{:016x}\";\n",
+        manifest_hash(Path::new("assets"))
+    )
+    .unwrap();
+    writeln!(
+        assets_file,
+        "/// Every image in assets/, packed into one texture\n\
+         pub const ATLAS_PNG: &[u8] = include_bytes!(concat!(env!(\"OUT_DIR\"), \"/atlas.png\"));\n\n\
+         /// Where each image is in the atlas, as x, y, width and height in pixels\n\
+         pub const ATLAS_RECTS: &[(&str, [u32; 4])] = &["
+    )
+    .unwrap();
+    for (name, rect) in &atlas.rects {
+        writeln!(assets_file, "    ({name:?}, {rect:?}),").unwrap();
+    }
+    writeln!(assets_file, "];\n").unwrap();
     assets_file
         .write_all(assets_struct.to_string().as_bytes())
         .expect("error writing struct to assets.rs file");
+    assets_file
+        .write_all(sprite_ids(&atlas.rects).to_string().as_bytes())
+        .expect("error writing SpriteId to assets.rs file");
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Why `name` can't be the name of a field, if it can't
+fn invalid_name(name: &str) -> Option<String> {
+    if !name.starts_with(|c: char| c.is_ascii_lowercase()) {
+        Some(format!("{name:?} doesn't start with a lowercase letter"))
+    } else if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_'))
+    {
+        Some(format!("{name:?} has {c:?} in it"))
+    } else if KEYWORDS.contains(&name) {
+        Some(format!("{name:?} is a Rust keyword"))
+    } else {
+        None
+    }
+}
+
+/// Everything in `dir` that would break the generated code, one readable
+/// message each
+fn validate(dir: &Path) -> Vec<String> {
+    let mut problems = vec![];
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return vec![format!("{}: can't be read: {e}", dir.display())],
+    };
+    let mut names: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for entry in entries {
+        let path = entry.unwrap().path();
+        let Some(file_name) = path.file_name().unwrap().to_str() else {
+            problems.push(format!("{}: the name isn't valid UTF-8", path.display()));
+            continue;
+        };
+        let name = if path.is_dir() {
+            problems.extend(validate(&path));
+            file_name
+        } else if let Some(stem) = file_name.strip_suffix(".png") {
+            if let Err(e) = image::open(&path) {
+                problems.push(format!("{}: not a readable PNG: {e}", path.display()));
+            }
+            stem
+        } else {
+            problems.push(format!(
+                "{}: not a .png file, only PNG images can be assets",
+                path.display()
+            ));
+            continue;
+        };
+        if let Some(reason) = invalid_name(name) {
+            problems.push(format!(
+                "{}: can't be turned into a Rust name, {reason}",
+                path.display()
+            ));
+        } else if dir == Path::new("assets") && name == "atlas" {
+            problems.push(format!(
+                "{}: \"atlas\" is taken by the atlas texture",
+                path.display()
+            ));
+        } else if let Some(other) = names.insert(name.to_string(), path.clone()) {
+            problems.push(format!(
+                "{} and {}: both would be named {name:?}",
+                other.display(),
+                path.display()
+            ));
+        }
+    }
+    problems.sort();
+    problems
+}
+
+/// `objects/tree_slalom` as `ObjectsTreeSlalom`
+fn variant_name(name: &str) -> String {
+    name.split(['/', '_']).map(capitalize_first).collect()
+}
+
+/// `SpriteId`, naming every sprite with its path under assets/
+fn sprite_ids(rects: &BTreeMap<String, [u32; 4]>) -> TokenStream {
+    let names: Vec<_> = rects
+        .keys()
+        .map(|n| n.trim_end_matches(".png").to_string())
+        .collect();
+    let variants: Vec<_> = names
+        .iter()
+        .map(|n| format_ident!("{}", variant_name(n)))
+        .collect();
+    let mut seen = BTreeMap::new();
+    for (name, variant) in names.iter().zip(&variants) {
+        if let Some(other) = seen.insert(variant.to_string(), name) {
+            eprintln!(
+                "error: assets/{other}.png and assets/{name}.png would both be SpriteId::{variant}"
+            );
+            std::process::exit(1);
+        }
+    }
+    let fields: Vec<_> = names
+        .iter()
+        .map(|n| {
+            let fields = n.split('/').map(|f| format_ident!("{f}"));
+            quote! { #(.#fields)* }
+        })
+        .collect();
+    let widths = rects.values().map(|r| r[2]);
+    let heights = rects.values().map(|r| r[3]);
+    let count = names.len();
+    quote! {
+        /// Names every sprite by its path under assets/, without the
+        /// extension, e.g. `"objects/tree"`, so files can refer to sprites
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+        pub enum SpriteId {
+            #(#[serde(rename = #names)] #variants),*
+        }
+
+        impl SpriteId {
+            pub const ALL: [SpriteId; #count] = [#(SpriteId::#variants),*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    #(SpriteId::#variants => #names),*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<SpriteId> {
+                match name {
+                    #(#names => Some(SpriteId::#variants),)*
+                    _ => None,
+                }
+            }
+
+            /// Width and height in pixels
+            pub fn size(self) -> (u32, u32) {
+                match self {
+                    #(SpriteId::#variants => (#widths, #heights)),*
+                }
+            }
+        }
+
+        impl Assets {
+            pub fn sprite(&self, id: SpriteId) -> &std::rc::Rc<Sprite> {
+                match id {
+                    #(SpriteId::#variants => &self #fields),*
+                }
+            }
+        }
+    }
 }
 
 fn capitalize_first(s: &str) -> String {
@@ -29,23 +224,129 @@ fn capitalize_first(s: &str) -> String {
         .collect()
 }
 
-fn project_dir() -> PathBuf {
-    Path::new(&std::env::var_os("CARGO_MANIFEST_DIR").unwrap()).to_path_buf()
+/// FNV-1a of the paths and contents of the files under `dir`, in sorted
+/// order so it doesn't depend on the file system
+fn manifest_hash(dir: &Path) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for path in files(dir) {
+        let name = path.to_string_lossy().replace('\\', "/");
+        for byte in name.bytes().chain(std::fs::read(&path).unwrap()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
 }
 
-fn visit_dir(path: &Path) -> (String, TokenStream) {
+/// Files under `dir`, sorted
+fn files(dir: &Path) -> Vec<PathBuf> {
+    fn visit(dir: &Path, out: &mut Vec<PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                visit(&path, out);
+            } else {
+                out.push(path);
+            }
+        }
+    }
+    let mut paths = vec![];
+    visit(dir, &mut paths);
+    paths.sort();
+    paths
+}
+
+/// Name of an image in the atlas, its path under assets/
+fn atlas_name(path: &Path) -> String {
+    path.strip_prefix("assets")
+        .unwrap()
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+struct Atlas {
+    image: RgbaImage,
+    rects: BTreeMap<String, [u32; 4]>,
+}
+
+/// Width of the atlas, unless an image is wider
+const ATLAS_WIDTH: u32 = 256;
+/// Transparent pixels around each image, so neighbours don't bleed in
+const ATLAS_PADDING: u32 = 1;
+
+/// Pack every PNG under `dir` into one image, in rows of images sorted by
+/// height
+fn pack_atlas(dir: &Path) -> Atlas {
+    let mut images: Vec<_> = files(dir)
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "png"))
+        .map(|path| {
+            let image = image::open(&path)
+                .unwrap_or_else(|e| panic!("error reading {}: {e}", path.display()))
+                .to_rgba8();
+            (atlas_name(&path), image)
+        })
+        .collect();
+    images.sort_by(|(a_name, a), (b_name, b)| b.height().cmp(&a.height()).then(a_name.cmp(b_name)));
+
+    let width = images
+        .iter()
+        .map(|(_, i)| i.width() + ATLAS_PADDING * 2)
+        .max()
+        .unwrap_or(0)
+        .max(ATLAS_WIDTH);
+    let mut positions = vec![];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for (_, image) in &images {
+        let (w, h) = (
+            image.width() + ATLAS_PADDING * 2,
+            image.height() + ATLAS_PADDING * 2,
+        );
+        if x + w > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        positions.push((x + ATLAS_PADDING, y + ATLAS_PADDING));
+        x += w;
+        row_height = row_height.max(h);
+    }
+
+    let mut atlas = RgbaImage::new(width, y + row_height);
+    let mut rects = BTreeMap::new();
+    for ((name, image), (x, y)) in images.into_iter().zip(positions) {
+        image::imageops::replace(&mut atlas, &image, x as i64, y as i64);
+        rects.insert(name, [x, y, image.width(), image.height()]);
+    }
+    Atlas {
+        image: atlas,
+        rects,
+    }
+}
+
+fn visit_dir(path: &Path, rects: &BTreeMap<String, [u32; 4]>, root: bool) -> (String, TokenStream) {
     let mut fields = vec![];
     let mut defs = vec![];
     let mut field_constructors = vec![];
+    let mut headless_constructors = vec![];
+    if root {
+        fields.push(quote! {
+            /// The texture every sprite is drawn from, when there's a window
+            pub atlas: Option<ggez::graphics::Image>
+        });
+        field_constructors.push(quote! { atlas: Some(atlas.clone()) });
+        headless_constructors.push(quote! { atlas: None });
+    }
     for entry in std::fs::read_dir(path).unwrap() {
         let entry = entry.unwrap();
         if entry.path().is_dir() {
-            let (field_type, def) = visit_dir(entry.path().as_path());
+            let (field_type, def) = visit_dir(entry.path().as_path(), rects, false);
             defs.push(def);
             let field_name = format_ident!("{}", field_type.to_lowercase());
             let field_type = format_ident!("{field_type}");
             fields.push(quote! { pub #field_name: #field_type });
-            field_constructors.push(quote! { #field_name: <#field_type>::new(ctx)? });
+            field_constructors.push(quote! { #field_name: <#field_type>::from_atlas(atlas) });
+            headless_constructors.push(quote! { #field_name: <#field_type>::headless() });
         } else {
             let field_name = format_ident!(
                 "{}",
@@ -56,12 +357,14 @@ fn visit_dir(path: &Path) -> (String, TokenStream) {
                     .to_string()
             );
 
-            let image_path = entry.path();
-            let image_path = project_dir().join(image_path);
-            let image_bytes = std::fs::read(&image_path).unwrap();
-            fields.push(quote! { pub #field_name : std::rc::Rc<ggez::graphics::Image> });
-            field_constructors
-                .push(quote! { #field_name: std::rc::Rc::new(ggez::graphics::Image::from_bytes(ctx, &[ #(#image_bytes,)*])?) })
+            let [x, y, width, height] = rects[&atlas_name(&entry.path())];
+            fields.push(quote! { pub #field_name : std::rc::Rc<Sprite> });
+            field_constructors.push(
+                quote! { #field_name: std::rc::Rc::new(Sprite::in_atlas(atlas, [#x, #y, #width, #height])) },
+            );
+            headless_constructors.push(
+                quote! { #field_name: std::rc::Rc::new(Sprite::headless([#x, #y, #width, #height])) },
+            )
         }
     }
     let struct_type = capitalize_first(&path.file_name().unwrap().to_string_lossy());
@@ -72,12 +375,18 @@ fn visit_dir(path: &Path) -> (String, TokenStream) {
         }
 
         impl #struct_name {
-            pub fn new(ctx: &mut ggez::Context) -> ggez::GameResult<#struct_name> {
-                Ok(
-                    #struct_name {
-                        #(#field_constructors),*
-                    }
-                )
+            /// Sprites cut out of the atlas loaded from `ATLAS_PNG`
+            pub fn from_atlas(atlas: &ggez::graphics::Image) -> #struct_name {
+                #struct_name {
+                    #(#field_constructors),*
+                }
+            }
+
+            /// Just the sizes of the images, for simulating without a window
+            pub fn headless() -> #struct_name {
+                #struct_name {
+                    #(#headless_constructors),*
+                }
             }
         }
 
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use ggez::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{load_toml, save_toml};
+use crate::events::{GameEvent, Subscriber};
+use crate::map::Course;
+use crate::player::{CollisionAction, TrickType};
+use crate::settings::Skin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Achievement {
+    LongHaul,
+    BigAir,
+    CleanSlalom,
+    YetiEscape,
+    LiftRider,
+}
+
+/// Distance for `Achievement::LongHaul`
+const LONG_HAUL_DISTANCE: u32 = 10_000;
+
+impl Achievement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::LongHaul => "Long Haul",
+            Achievement::BigAir => "Big Air",
+            Achievement::CleanSlalom => "Clean Slalom",
+            Achievement::YetiEscape => "Close Call",
+            Achievement::LiftRider => "Lift Rider",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::LongHaul => "Ski a distance of 10,000 in one run",
+            Achievement::BigAir => "Land a flip off a ramp",
+            Achievement::CleanSlalom => "Finish the slalom without missing a gate",
+            Achievement::YetiEscape => "Get away from the yeti",
+            Achievement::LiftRider => "Ride the chairlift to the top",
+        }
+    }
+
+    /// Skin the achievement unlocks, if any
+    pub fn unlocks(&self) -> Option<Skin> {
+        match self {
+            Achievement::LongHaul => Some(Skin::Gold),
+            Achievement::YetiEscape => Some(Skin::Ice),
+            Achievement::CleanSlalom => Some(Skin::Crimson),
+            Achievement::BigAir | Achievement::LiftRider => None,
+        }
+    }
+}
+
+/// Unlocked achievements, stored as TOML next to the config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+    /// Unlocked since the last call to `take_new`
+    #[serde(skip)]
+    new: Vec<Achievement>,
+    /// Whether the skier launched off a ramp on the current jump
+    #[serde(skip)]
+    large_jump: bool,
+}
+
+impl Achievements {
+    const FILE_NAME: &'static str = "achievements.toml";
+
+    pub fn path(ctx: &Context) -> PathBuf {
+        ctx.fs.user_config_dir().join(Self::FILE_NAME)
+    }
+
+    /// Load the achievements at `path`. A missing file means none are unlocked.
+    pub fn load(path: &Path) -> Result<Self> {
+        load_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        save_toml(self, path)
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    /// Skins the player can choose from
+    pub fn skins(&self) -> Vec<Skin> {
+        Skin::ALL
+            .into_iter()
+            .filter(|skin| {
+                *skin == Skin::Classic || self.unlocked.iter().any(|a| a.unlocks() == Some(*skin))
+            })
+            .collect()
+    }
+
+    /// Achievements unlocked since the last call
+    pub fn take_new(&mut self) -> Vec<Achievement> {
+        std::mem::take(&mut self.new)
+    }
+
+    /// Start a new run
+    pub fn reset_run(&mut self) {
+        self.large_jump = false;
+    }
+
+    fn unlock(&mut self, achievement: Achievement) {
+        if !self.is_unlocked(achievement) {
+            self.unlocked.push(achievement);
+            self.new.push(achievement);
+        }
+    }
+}
+
+impl Subscriber for Achievements {
+    fn on_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::Collided(_, CollisionAction::JumpLarge) => self.large_jump = true,
+            GameEvent::TrickSucceeded(TrickType::Flip) if self.large_jump => {
+                self.unlock(Achievement::BigAir)
+            }
+            GameEvent::Landed | GameEvent::Fell => self.large_jump = false,
+            GameEvent::DistanceMilestone(distance) if distance >= LONG_HAUL_DISTANCE => {
+                self.unlock(Achievement::LongHaul)
+            }
+            GameEvent::CourseFinished {
+                course: Course::Slalom,
+                missed_gates: 0,
+            } => self.unlock(Achievement::CleanSlalom),
+            GameEvent::YetiEscaped => self.unlock(Achievement::YetiEscape),
+            GameEvent::LiftTopReached => self.unlock(Achievement::LiftRider),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::objects::ObjectKind;
+
+    #[test]
+    fn flip_off_ramp_unlocks_big_air_and_persists() {
+        let mut achievements = Achievements::default();
+        // a flip off a small bump doesn't count
+        for event in [
+            GameEvent::Collided(ObjectKind::Bump, CollisionAction::JumpSmall),
+            GameEvent::TrickSucceeded(TrickType::Flip),
+            GameEvent::Landed,
+        ] {
+            achievements.on_event(&event);
+        }
+        assert!(achievements.take_new().is_empty());
+
+        for event in [
+            GameEvent::Collided(ObjectKind::Ramp, CollisionAction::JumpLarge),
+            GameEvent::TrickSucceeded(TrickType::Flip),
+            GameEvent::Landed,
+        ] {
+            achievements.on_event(&event);
+        }
+        assert_eq!(achievements.take_new(), vec![Achievement::BigAir]);
+
+        let toml = toml::to_string_pretty(&achievements).unwrap();
+        let loaded: Achievements = toml::from_str(&toml).unwrap();
+        assert!(loaded.is_unlocked(Achievement::BigAir));
+        assert!(!loaded.is_unlocked(Achievement::LongHaul));
+    }
+
+    #[test]
+    fn achievements_unlock_skins() {
+        let mut achievements = Achievements::default();
+        assert_eq!(achievements.skins(), vec![Skin::Classic]);
+        achievements.on_event(&GameEvent::DistanceMilestone(10_000));
+        achievements.on_event(&GameEvent::CourseFinished {
+            course: Course::Slalom,
+            missed_gates: 1,
+        });
+        assert_eq!(achievements.skins(), vec![Skin::Classic, Skin::Gold]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::rc::Rc;
+
+use crate::assets::{Assets, Sprite};
+use crate::player::Pose;
+
+/// What a clip does after its last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playback {
+    /// Start again from the first frame
+    Loop,
+    /// Stay on the last frame
+    Once,
+}
+
+/// One frame of a clip, shown for `ticks` ticks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame<T: 'static> {
+    pub value: T,
+    pub ticks: u32,
+}
+
+/// Frames played one after another, e.g. sprites or poses. Clips are plain
+/// data, so the whole animation is in the table that defines it.
+#[derive(Debug, PartialEq)]
+pub struct Clip<T: 'static> {
+    pub name: &'static str,
+    pub frames: &'static [Frame<T>],
+    pub playback: Playback,
+}
+
+impl<T> Clip<T> {
+    /// Ticks it takes to play every frame once
+    pub fn duration(&self) -> u32 {
+        self.frames.iter().map(|f| f.ticks).sum()
+    }
+
+    /// Index of the frame showing `tick` ticks into the clip
+    pub fn index_at(&self, tick: u32) -> usize {
+        let duration = self.duration();
+        let mut tick = match self.playback {
+            Playback::Loop if duration > 0 => tick % duration,
+            _ => tick,
+        };
+        for (i, frame) in self.frames.iter().enumerate() {
+            if tick < frame.ticks {
+                return i;
+            }
+            tick -= frame.ticks;
+        }
+        self.frames.len() - 1
+    }
+
+    pub fn frame_at(&self, tick: u32) -> &T {
+        &self.frames[self.index_at(tick)].value
+    }
+
+    /// A clip played once has finished after `tick` ticks. Looping clips
+    /// never finish.
+    pub fn is_done(&self, tick: u32) -> bool {
+        self.playback == Playback::Once && tick >= self.duration()
+    }
+}
+
+pub const fn frame<T>(value: T, ticks: u32) -> Frame<T> {
+    Frame { value, ticks }
+}
+
+/// Finds a sprite in the assets, so clips of sprites can be written as
+/// constants
+pub type SpriteRef = fn(&Assets) -> &Rc<Sprite>;
+
+pub const fn sprite(sprite: SpriteRef, ticks: u32) -> Frame<SpriteRef> {
+    Frame {
+        value: sprite,
+        ticks,
+    }
+}
+
+/// A clip of sprites being played, e.g. by an object on the map
+#[derive(Debug, Clone)]
+pub struct Animation {
+    clip: &'static Clip<SpriteRef>,
+    sprites: Vec<Rc<Sprite>>,
+    tick: u32,
+}
+
+impl Animation {
+    pub fn new(clip: &'static Clip<SpriteRef>, assets: &Assets) -> Self {
+        Self {
+            clip,
+            sprites: clip
+                .frames
+                .iter()
+                .map(|f| (f.value)(assets).clone())
+                .collect(),
+            tick: 0,
+        }
+    }
+
+    pub fn clip(&self) -> &'static Clip<SpriteRef> {
+        self.clip
+    }
+
+    pub fn update(&mut self) {
+        self.tick += 1;
+    }
+
+    pub fn sprite(&self) -> &Rc<Sprite> {
+        &self.sprites[self.clip.index_at(self.tick)]
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.clip.is_done(self.tick)
+    }
+}
+
+/// A flip, as long as `FLIP_FRAMES` so it comes round as the skier lands
+pub const SKIER_FLIP: Clip<Pose> = Clip {
+    name: "skier_flip",
+    frames: &[
+        frame(Pose::Flip1, 13),
+        frame(Pose::Flip2, 12),
+        frame(Pose::Flip3, 13),
+        frame(Pose::Flip4, 12),
+    ],
+    playback: Playback::Once,
+};
+
+/// The yeti lumbering after a skier
+pub const YETI_RUN: Clip<SpriteRef> = Clip {
+    name: "yeti_run",
+    frames: &[
+        sprite(|a| &a.npcs.snowman.snowman1, 8),
+        sprite(|a| &a.npcs.snowman.snowman2, 8),
+        sprite(|a| &a.npcs.snowman.snowman3, 8),
+        sprite(|a| &a.npcs.snowman.snowman4, 8),
+    ],
+    playback: Playback::Loop,
+};
+
+/// The yeti eating a skier, ending satisfied
+pub const YETI_EAT: Clip<SpriteRef> = Clip {
+    name: "yeti_eat",
+    frames: &[
+        sprite(|a| &a.npcs.snowman.snowman_eat1, 12),
+        sprite(|a| &a.npcs.snowman.snowman_eat2, 12),
+        sprite(|a| &a.npcs.snowman.snowman_eat3, 12),
+        sprite(|a| &a.npcs.snowman.snowman_eat4, 12),
+        sprite(|a| &a.npcs.snowman.snowman_eat5, 12),
+        sprite(|a| &a.npcs.snowman.snowman_eat6, 12),
+    ],
+    playback: Playback::Once,
+};
+
+/// A dog barking, then wagging
+pub const DOG_WAG: Clip<SpriteRef> = Clip {
+    name: "dog_wag",
+    frames: &[
+        sprite(|a| &a.npcs.dog.dog_sit, 40),
+        sprite(|a| &a.npcs.dog.dog_l, 10),
+        sprite(|a| &a.npcs.dog.dog_r, 10),
+        sprite(|a| &a.npcs.dog.dog_l, 10),
+        sprite(|a| &a.npcs.dog.dog_r, 10),
+    ],
+    playback: Playback::Loop,
+};
+
+/// A beginner who keeps falling over and getting back up
+pub const NOOB_FALL: Clip<SpriteRef> = Clip {
+    name: "noob_fall",
+    frames: &[
+        sprite(|a| &a.npcs.noob.noob_1, 90),
+        sprite(|a| &a.npcs.noob.noob_2, 8),
+        sprite(|a| &a.npcs.noob.noob_3, 60),
+    ],
+    playback: Playback::Loop,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIP: Clip<char> = Clip {
+        name: "test",
+        frames: &[frame('a', 2), frame('b', 3)],
+        playback: Playback::Loop,
+    };
+
+    #[test]
+    fn plays_frames_for_their_ticks() {
+        let frames: String = (0..12).map(|t| *CLIP.frame_at(t)).collect();
+        assert_eq!(frames, "aabbbaabbbaa");
+        assert!(!CLIP.is_done(100));
+
+        let once = Clip {
+            playback: Playback::Once,
+            ..CLIP
+        };
+        let frames: String = (0..8).map(|t| *once.frame_at(t)).collect();
+        assert_eq!(frames, "aabbbbbb");
+        assert!(!once.is_done(4));
+        assert!(once.is_done(5));
+    }
+
+    #[test]
+    fn animates_sprites() {
+        let assets = Assets::headless();
+        let mut eat = Animation::new(&YETI_EAT, &assets);
+        assert!(Rc::ptr_eq(eat.sprite(), &assets.npcs.snowman.snowman_eat1));
+        for _ in 0..YETI_EAT.duration() {
+            eat.update();
+        }
+        assert!(eat.is_done());
+        assert!(Rc::ptr_eq(eat.sprite(), &assets.npcs.snowman.snowman_eat6));
+    }
+}
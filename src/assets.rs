@@ -1 +1,133 @@
+use ggez::context::Has;
+use ggez::graphics::{Canvas, DrawParam, Drawable, GraphicsContext, Image, Rect};
+use ggez::{Context, GameResult};
+
 include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+impl Assets {
+    /// Load the atlas and cut every sprite out of it
+    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+        let atlas = Image::from_bytes(ctx, ATLAS_PNG)?;
+        Ok(Self::from_atlas(&atlas))
+    }
+}
+
+/// A part of the atlas to draw, or only its size when simulating without a
+/// window
+#[derive(Debug)]
+pub struct Sprite {
+    atlas: Option<Image>,
+    /// Where the sprite is in the atlas, in pixels
+    rect: [u32; 4],
+    /// The same as a fraction of the atlas' size
+    src: Rect,
+    width: u32,
+    height: u32,
+}
+
+impl Sprite {
+    /// The sprite at `[x, y, width, height]` in the atlas, in pixels
+    pub fn in_atlas(atlas: &Image, rect: [u32; 4]) -> Self {
+        let [x, y, width, height] = rect;
+        let (atlas_width, atlas_height) = (atlas.width() as f32, atlas.height() as f32);
+        Self {
+            atlas: Some(atlas.clone()),
+            rect,
+            src: Rect::new(
+                x as f32 / atlas_width,
+                y as f32 / atlas_height,
+                width as f32 / atlas_width,
+                height as f32 / atlas_height,
+            ),
+            width,
+            height,
+        }
+    }
+
+    /// A sprite with nothing to draw on the GPU, at `rect` in `ATLAS_PNG`
+    pub fn headless(rect: [u32; 4]) -> Self {
+        Self {
+            atlas: None,
+            rect,
+            src: Rect::one(),
+            width: rect[2],
+            height: rect[3],
+        }
+    }
+
+    /// Where the sprite is in `ATLAS_PNG`, as x, y, width and height in
+    /// pixels
+    pub fn rect(&self) -> [u32; 4] {
+        self.rect
+    }
+
+    /// `param` drawing this sprite out of the atlas, e.g. as an instance of
+    /// an `InstanceArray`
+    pub fn param(&self, param: DrawParam) -> DrawParam {
+        param.src(self.src)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl Drawable for Sprite {
+    fn draw(&self, canvas: &mut Canvas, param: impl Into<DrawParam>) {
+        if let Some(atlas) = &self.atlas {
+            atlas.draw(canvas, self.param(param.into()));
+        }
+    }
+
+    fn dimensions(&self, _gfx: &impl Has<GraphicsContext>) -> Option<Rect> {
+        Some(Rect::new(0., 0., self.width as f32, self.height as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn atlas_rects_hold_the_original_images() {
+        let atlas = image::load_from_memory(ATLAS_PNG).unwrap().to_rgba8();
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        for &(name, [x, y, width, height]) in ATLAS_RECTS {
+            let original = image::open(assets.join(name)).unwrap().to_rgba8();
+            let packed = image::imageops::crop_imm(&atlas, x, y, width, height).to_image();
+            assert!(packed == original, "{name} differs in the atlas");
+        }
+        let headless = Assets::headless();
+        let rect = ATLAS_RECTS
+            .iter()
+            .find(|(n, _)| *n == "player/skier_down.png");
+        assert_eq!(rect.unwrap().1[2..], [
+            headless.player.skier_down.width(),
+            headless.player.skier_down.height()
+        ]);
+    }
+
+    #[test]
+    fn sprite_ids_name_every_sprite() {
+        let assets = Assets::headless();
+        assert_eq!(SpriteId::ALL.len(), ATLAS_RECTS.len());
+        for id in SpriteId::ALL {
+            assert_eq!(SpriteId::from_name(id.name()), Some(id));
+            let sprite = assets.sprite(id);
+            assert_eq!(id.size(), (sprite.width(), sprite.height()));
+        }
+        assert!(Rc::ptr_eq(
+            assets.sprite(SpriteId::ObjectsTreeSlalom),
+            &assets.objects.tree_slalom
+        ));
+        assert_eq!(SpriteId::from_name("objects/tree_slalom.png"), None);
+        let id: SpriteId = serde_json::from_str("\"npcs/dog/dog_sit\"").unwrap();
+        assert_eq!(id, SpriteId::NpcsDogDogSit);
+    }
+}
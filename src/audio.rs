@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use rodio::source::{from_iter, SineWave, Source};
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+
+use crate::events::{GameEvent, Subscriber};
+use crate::settings::Settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Turn,
+    Jump,
+    JumpLarge,
+    Land,
+    Fall,
+    Trick,
+    Gate,
+    MissedGate,
+    Yeti,
+    Achievement,
+}
+
+impl Sound {
+    fn for_event(event: &GameEvent) -> Option<Sound> {
+        match event {
+            GameEvent::Turned => Some(Sound::Turn),
+            GameEvent::Jumped { large: false } => Some(Sound::Jump),
+            GameEvent::Jumped { large: true } => Some(Sound::JumpLarge),
+            GameEvent::Landed => Some(Sound::Land),
+            GameEvent::TrickSucceeded(_) => Some(Sound::Trick),
+            GameEvent::Fell => Some(Sound::Fall),
+            GameEvent::GatePassed => Some(Sound::Gate),
+            GameEvent::GateMissed => Some(Sound::MissedGate),
+            GameEvent::YetiAppeared | GameEvent::YetiCaught => Some(Sound::Yeti),
+            GameEvent::AchievementUnlocked(_) => Some(Sound::Achievement),
+            _ => None,
+        }
+    }
+
+    /// Notes of the effect as (frequency, milliseconds)
+    fn notes(&self) -> &'static [(f32, u64)] {
+        match self {
+            Sound::Turn => &[(880., 20)],
+            Sound::Jump => &[(440., 40), (660., 60)],
+            Sound::JumpLarge => &[(440., 40), (660., 40), (880., 80)],
+            Sound::Land => &[(220., 50)],
+            Sound::Fall => &[(330., 80), (220., 80), (110., 160)],
+            Sound::Trick => &[(523., 60), (659., 60), (784., 60), (1047., 120)],
+            Sound::Gate => &[(784., 40), (1047., 60)],
+            Sound::MissedGate => &[(196., 120)],
+            Sound::Yeti => &[(98., 150), (82., 150), (65., 300)],
+            Sound::Achievement => &[(659., 80), (784., 80), (1047., 80), (1319., 200)],
+        }
+    }
+}
+
+/// Something that can play sounds. The game talks to it through [`Audio`].
+pub trait AudioBackend {
+    fn play(&mut self, sound: Sound, volume: f32);
+    fn set_music_volume(&mut self, volume: f32);
+    fn pause_music(&mut self, paused: bool);
+}
+
+/// Backend for machines without a sound device
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn play(&mut self, _sound: Sound, _volume: f32) {}
+
+    fn set_music_volume(&mut self, _volume: f32) {}
+
+    fn pause_music(&mut self, _paused: bool) {}
+}
+
+/// Plays synthesized effects and music on the default output device
+pub struct RodioAudio {
+    // the stream stops playing when dropped
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    music: Sink,
+}
+
+impl RodioAudio {
+    const MELODY: [(f32, u64); 8] = [
+        (262., 300),
+        (330., 300),
+        (392., 300),
+        (330., 300),
+        (294., 300),
+        (349., 300),
+        (440., 300),
+        (349., 300),
+    ];
+
+    pub fn new() -> Result<Self> {
+        let (stream, handle) = OutputStream::try_default()?;
+        let music = Sink::try_new(&handle)?;
+        music.append(tune(&Self::MELODY, 0.05).repeat_infinite());
+        Ok(Self {
+            _stream: stream,
+            handle,
+            music,
+        })
+    }
+}
+
+impl AudioBackend for RodioAudio {
+    fn play(&mut self, sound: Sound, volume: f32) {
+        // a sound that can't be played isn't worth stopping the game for
+        let _ = self.handle.play_raw(tune(sound.notes(), volume * 0.2));
+    }
+
+    fn set_music_volume(&mut self, volume: f32) {
+        self.music.set_volume(volume);
+    }
+
+    fn pause_music(&mut self, paused: bool) {
+        if paused {
+            self.music.pause();
+        } else {
+            self.music.play();
+        }
+    }
+}
+
+/// Short beeps in the spirit of the original's PC speaker
+fn tune(notes: &'static [(f32, u64)], amplitude: f32) -> impl Source<Item = f32> + Send {
+    from_iter(notes.iter().map(move |(frequency, ms)| {
+        SineWave::new(*frequency)
+            .take_duration(Duration::from_millis(*ms))
+            .amplify(amplitude)
+    }))
+}
+
+/// Plays sounds for game events at the volume from the settings
+pub struct Audio {
+    backend: Box<dyn AudioBackend>,
+    volume: f32,
+}
+
+impl Audio {
+    /// Use the default sound device, or no sound at all if there isn't one
+    pub fn new(settings: &Settings) -> Self {
+        let backend: Box<dyn AudioBackend> = match RodioAudio::new() {
+            Ok(backend) => Box::new(backend),
+            Err(e) => {
+                eprintln!("no audio device, sound is disabled: {e:#}");
+                Box::new(NullAudio)
+            }
+        };
+        Self::with_backend(backend, settings)
+    }
+
+    pub fn with_backend(backend: Box<dyn AudioBackend>, settings: &Settings) -> Self {
+        let mut audio = Self {
+            backend,
+            volume: 0.,
+        };
+        audio.apply_settings(settings);
+        audio
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.volume = settings.volume as f32 / Settings::MAX_VOLUME as f32;
+        let music = settings.music_volume as f32 / Settings::MAX_VOLUME as f32;
+        self.backend.set_music_volume(music);
+    }
+
+    pub fn pause(&mut self, paused: bool) {
+        self.backend.pause_music(paused);
+    }
+}
+
+impl Subscriber for Audio {
+    fn on_event(&mut self, event: &GameEvent) {
+        if let Some(sound) = Sound::for_event(event) {
+            self.backend.play(sound, self.volume);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[derive(Default, Clone)]
+    struct Recorder(Rc<RefCell<Vec<(Sound, f32)>>>);
+
+    impl AudioBackend for Recorder {
+        fn play(&mut self, sound: Sound, volume: f32) {
+            self.0.borrow_mut().push((sound, volume));
+        }
+
+        fn set_music_volume(&mut self, _volume: f32) {}
+
+        fn pause_music(&mut self, _paused: bool) {}
+    }
+
+    #[test]
+    fn plays_sounds_for_events_at_settings_volume() {
+        let recorder = Recorder::default();
+        let settings = Settings {
+            volume: 5,
+            ..Default::default()
+        };
+        let mut audio = Audio::with_backend(Box::new(recorder.clone()), &settings);
+        audio.on_event(&GameEvent::Jumped { large: true });
+        audio.on_event(&GameEvent::Fell);
+        assert_eq!(*recorder.0.borrow(), vec![
+            (Sound::JumpLarge, 0.5),
+            (Sound::Fall, 0.5)
+        ]);
+    }
+}
@@ -0,0 +1,61 @@
+//! The RL environment over stdin and stdout, one JSON request per line and
+//! one JSON response per line:
+//!
+//! ```text
+//! {"configure":{"difficulty":"hard","rewards":{"fall":-50}}}  -> {"ok":true}
+//! {"reset":{"seed":1}}                                        -> observation
+//! {"step":"left"}                                             -> step
+//! ```
+//!
+//! Anything that goes wrong is answered with `{"error":"..."}`.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use skifree_rs::env::{Control, Env, EnvConfig};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    /// Change the config, taking effect from the next reset
+    Configure(EnvConfig),
+    Reset {
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Step(Control),
+}
+
+fn respond(env: &mut Env, line: &str) -> Result<Value> {
+    Ok(match serde_json::from_str(line)? {
+        Request::Configure(config) => {
+            *env = Env::new(config);
+            json!({ "ok": true })
+        }
+        Request::Reset { seed } => to_value(env.reset(seed))?,
+        Request::Step(control) => to_value(env.step(control))?,
+    })
+}
+
+fn to_value(value: impl Serialize) -> Result<Value> {
+    Ok(serde_json::to_value(value)?)
+}
+
+fn main() -> Result<()> {
+    let mut env = Env::new(Default::default());
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response =
+            respond(&mut env, &line).unwrap_or_else(|e| json!({ "error": e.to_string() }));
+        serde_json::to_writer(&mut stdout, &response)?;
+        writeln!(stdout)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
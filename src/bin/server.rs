@@ -0,0 +1,45 @@
+#![feature(never_type)]
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use rand::rngs::OsRng;
+use rand::Rng;
+use skifree_rs::net::server::Server;
+use skifree_rs::net::DEFAULT_PORT;
+use skifree_rs::settings::Difficulty;
+
+const USAGE: &str = "usage: skifree-server [address] [--seed N] [--difficulty easy|normal|hard]";
+
+fn main() -> Result<!> {
+    let mut addr = format!("0.0.0.0:{DEFAULT_PORT}");
+    let mut seed = OsRng.gen();
+    let mut difficulty = Difficulty::Normal;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{arg} needs a value"));
+        match arg.as_str() {
+            "--seed" => seed = value()?.parse().context("invalid seed")?,
+            "--difficulty" => {
+                let name = value()?;
+                difficulty = *Difficulty::ALL
+                    .iter()
+                    .find(|d| d.name() == name)
+                    .ok_or_else(|| anyhow!("unknown difficulty {name}"))?;
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ if arg.starts_with('-') => bail!("unknown option {arg}\n{USAGE}"),
+            _ => addr = arg,
+        }
+    }
+
+    let server = Server::bind(&addr, seed, difficulty)?;
+    eprintln!(
+        "racing on slope {seed} ({}) at {}",
+        difficulty.name(),
+        server.local_addr()?
+    );
+    server.run()
+}
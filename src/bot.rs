@@ -0,0 +1,218 @@
+use std::f32::consts::{FRAC_PI_4, FRAC_PI_6};
+
+use ggez::graphics::Rect;
+
+use crate::events::{GameEvent, Subscriber};
+use crate::map::objects::Object;
+use crate::map::{wrap_x, Map};
+use crate::player::{CollisionAction, Player, Pose, TrickType};
+use crate::util::vec2_from_angle;
+
+/// Ticks ahead the bot looks for what it's about to run into
+const LOOKAHEAD_TICKS: u32 = 45;
+/// Ticks between the points checked along each path
+const LOOKAHEAD_STEP: u32 = 3;
+/// Room the bot leaves on either side of an obstacle
+const MARGIN: f32 = 4.;
+/// Headings the bot picks from, from hard left to hard right
+const HEADINGS: [i8; 5] = [-2, -1, 0, 1, 2];
+/// Bigger tricks first
+const TRICKS: [TrickType; 3] = [TrickType::Flip, TrickType::Trick2, TrickType::Trick1];
+
+/// Autopilot that skis with the same controls as a player: it steers
+/// around whatever would make it fall, aims for jumps and shows off in the
+/// air with whatever trick it has time to land
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bot {
+    /// The yeti is after us
+    fleeing: bool,
+}
+
+impl Bot {
+    pub fn update(&self, player: &mut Player, map: &Map) {
+        player.boost(self.fleeing);
+        if let Some(frames) = player.jump_frame() {
+            let trick = TRICKS.into_iter().find(|t| t.required_frames() <= frames);
+            if let Some(trick) = trick {
+                player.do_trick(trick);
+            }
+            return;
+        }
+        let Some(current) = heading(player) else {
+            return;
+        };
+        let target = self.choose_heading(player, map, current);
+        if target < current {
+            player.left();
+        } else if target > current {
+            player.right();
+        }
+    }
+
+    /// The heading with the best path ahead of the skier
+    fn choose_heading(&self, player: &Player, map: &Map, current: i8) -> i8 {
+        let position = player.position();
+        let reach = LOOKAHEAD_TICKS as f32 * player.speed();
+        let nearby: Vec<_> = map
+            .objects()
+            .iter()
+            .filter(|o| o.collision_action != CollisionAction::Nothing)
+            .filter(|o| {
+                let x = wrap_x(o.position.x, position.x);
+                (x - position.x).abs() < reach + 100.
+                    && o.position.y > position.y - 100.
+                    && o.position.y < position.y + reach + 100.
+            })
+            .collect();
+        HEADINGS
+            .into_iter()
+            .max_by_key(|&h| score_path(player, &nearby, h) - 2 * (h - current).abs() as i32)
+            .unwrap_or(0)
+    }
+}
+
+/// How good it'd be to keep going in the heading: falling is terrible,
+/// jumping is great, going straight down is fastest
+fn score_path(player: &Player, nearby: &[&Object], heading: i8) -> i32 {
+    let velocity = vec2_from_angle(angle(heading)) * player.speed();
+    let hitbox = player.hitbox();
+    let score = -5 * heading.abs() as i32;
+    for tick in (LOOKAHEAD_STEP..=LOOKAHEAD_TICKS).step_by(LOOKAHEAD_STEP as usize) {
+        let offset = velocity * tick as f32;
+        // tall enough not to skip over anything between steps
+        let ahead = Rect::new(
+            hitbox.x + offset.x - MARGIN,
+            hitbox.y + offset.y,
+            hitbox.w + MARGIN * 2.,
+            hitbox.h + velocity.y * LOOKAHEAD_STEP as f32,
+        );
+        for o in nearby {
+            let mut obstacle = o.hitbox();
+            obstacle.x = wrap_x(obstacle.x, ahead.x);
+            if !obstacle.overlaps(&ahead) {
+                continue;
+            }
+            // sooner is worse, and nothing after it matters
+            match o.collision_action {
+                CollisionAction::Fall
+                | CollisionAction::Glance
+                | CollisionAction::Stumble
+                | CollisionAction::Deflect
+                | CollisionAction::Ride => {
+                    return score - 1000 + tick as i32;
+                }
+                CollisionAction::JumpLarge => return score + 60,
+                CollisionAction::JumpSmall => return score + 20,
+                // slows the skier down
+                CollisionAction::Hop => return score - 10,
+                CollisionAction::Nothing => {}
+            }
+        }
+    }
+    score
+}
+
+/// Heading of an upright skier, counting 30 and 45 degree turns from
+/// straight down. Stopped skiers count as facing sideways.
+fn heading(player: &Player) -> Option<i8> {
+    match player.pose() {
+        Pose::LeftStop | Pose::LeftMove => Some(-3),
+        Pose::Left45 => Some(-2),
+        Pose::Left30 => Some(-1),
+        Pose::Down => Some(0),
+        Pose::Right30 => Some(1),
+        Pose::Right45 => Some(2),
+        Pose::RightStop | Pose::RightMove => Some(3),
+        _ => None,
+    }
+}
+
+impl Subscriber for Bot {
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::YetiAppeared => self.fleeing = true,
+            GameEvent::YetiEscaped | GameEvent::YetiCaught => self.fleeing = false,
+            _ => {}
+        }
+    }
+}
+
+fn angle(heading: i8) -> f32 {
+    match heading {
+        -2 => -FRAC_PI_4,
+        -1 => -FRAC_PI_6,
+        1 => FRAC_PI_6,
+        2 => FRAC_PI_4,
+        _ => 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Simulation;
+    use crate::settings::Difficulty;
+
+    /// What happened on a run
+    #[derive(Debug, Default)]
+    struct Stats {
+        falls: u32,
+        jumps: u32,
+        tricks: u32,
+    }
+
+    fn bot_run(seed: u64, difficulty: Difficulty) -> (Simulation, Stats) {
+        let mut sim = Simulation::new(seed, difficulty);
+        let mut stats = Stats::default();
+        let mut bot = Bot::default();
+        while !sim.is_over() && sim.tick() < 20_000 {
+            bot.update(&mut sim.skier.player, &sim.map);
+            for event in sim.step() {
+                bot.on_event(&event);
+                match event {
+                    GameEvent::Fell => stats.falls += 1,
+                    GameEvent::Jumped { .. } => stats.jumps += 1,
+                    GameEvent::TrickSucceeded(_) => stats.tricks += 1,
+                    _ => {}
+                }
+            }
+        }
+        (sim, stats)
+    }
+
+    #[test]
+    fn bot_skis_down_the_slope() {
+        let mut falls = 0;
+        for seed in 0..5 {
+            let (sim, stats) = bot_run(seed, Difficulty::Normal);
+            assert!(sim.skier.progress.is_finished(), "seed {seed}: {stats:?}");
+            assert!(stats.tricks <= stats.jumps);
+            falls += stats.falls;
+        }
+        assert!(falls < 15, "fell {falls} times");
+    }
+
+    #[test]
+    fn bot_jumps_and_lands_tricks() {
+        let (jumps, tricks) = (0..5)
+            .map(|seed| bot_run(seed, Difficulty::Hard).1)
+            .fold((0, 0), |(j, t), s| (j + s.jumps, t + s.tricks));
+        assert!(jumps > 0);
+        assert!(tricks > 0);
+    }
+
+    /// Thousands of runs on random slopes, to shake out panics and stuck
+    /// skiers. Run with `cargo test --release -- --ignored soak`.
+    #[test]
+    #[ignore]
+    fn soak() {
+        for seed in 0..2000 {
+            let difficulty = Difficulty::ALL[seed as usize % 3];
+            let (sim, stats) = bot_run(seed, difficulty);
+            assert!(
+                sim.is_over() || sim.skier.player.distance() > 1000.,
+                "seed {seed} stuck: {stats:?}"
+            );
+        }
+    }
+}
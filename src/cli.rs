@@ -0,0 +1,332 @@
+//! Command line options of the game
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context as _, Result};
+
+use crate::map::Course;
+use crate::net;
+use crate::net::broadcast;
+use crate::settings::Difficulty;
+use crate::telemetry::Format;
+
+pub const USAGE: &str = "\
+usage: skifree-rs [options]
+
+  --seed N               ski the slope generated from N
+  --course NAME          start on slalom, freestyle or tree_slalom
+  --difficulty NAME      easy, normal or hard, instead of the one in the config
+  --replay PATH          play back a run recorded with --record
+  --record PATH          save the run's inputs to PATH on quit
+  --telemetry PATH       log every tick of the run to PATH, a .csv or .jsonl file
+  --headless             simulate without a window, with the replay or a bot
+  --ticks N              quit after N ticks
+  --snapshot tick=N,...  save PNGs of the run at those ticks, without a window
+  --export PATH          save the --replay as PATH.gif, or as PNGs in the directory PATH
+  --frames A..B          export ticks A to B, not including B
+  --scale N              make exported pixels N times as big
+  --no-hud               export without the HUD
+  --window WxH           size of the window, e.g. 960x1280
+  --fullscreen           start in fullscreen
+  --config PATH          config file to use instead of the one in the user's config dir
+  --demo                 let a bot ski until a key is pressed
+  --connect HOST[:PORT]  race on a server
+  --broadcast [ADDRESS]  let spectators watch, on 127.0.0.1:7879 by default
+  --spectate [ADDRESS]   watch a broadcasting game
+  --version              print the version and the hash of the assets
+  --help                 print this";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub seed: Option<u64>,
+    pub course: Option<Course>,
+    pub difficulty: Option<Difficulty>,
+    pub replay: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub telemetry: Option<PathBuf>,
+    pub headless: bool,
+    pub ticks: Option<u32>,
+    /// Ticks to save a picture of the run at
+    pub snapshot: Vec<u32>,
+    pub export: Option<PathBuf>,
+    /// Ticks to export
+    pub frames: Option<Range<u32>>,
+    pub scale: Option<u32>,
+    pub no_hud: bool,
+    /// Width and height of the window
+    pub window: Option<(f32, f32)>,
+    pub fullscreen: bool,
+    pub config: Option<PathBuf>,
+    pub demo: bool,
+    pub connect: Option<String>,
+    pub broadcast: Option<String>,
+    pub spectate: Option<String>,
+    pub version: bool,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parse the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("{arg} needs a value"));
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = Some(
+                        seed.parse()
+                            .with_context(|| format!("invalid seed {seed}"))?,
+                    );
+                }
+                "--course" => {
+                    let name = value()?;
+                    let course = Course::ALL.into_iter().find(|c| c.id() == name);
+                    options.course = Some(course.ok_or_else(|| {
+                        anyhow!("unknown course {name}, expected slalom, freestyle or tree_slalom")
+                    })?);
+                }
+                "--difficulty" => {
+                    let name = value()?;
+                    let difficulty = Difficulty::ALL.into_iter().find(|d| d.name() == name);
+                    options.difficulty = Some(difficulty.ok_or_else(|| {
+                        anyhow!("unknown difficulty {name}, expected easy, normal or hard")
+                    })?);
+                }
+                "--replay" => options.replay = Some(value()?.into()),
+                "--record" => options.record = Some(value()?.into()),
+                "--telemetry" => {
+                    let path = PathBuf::from(value()?);
+                    Format::of(&path)?;
+                    options.telemetry = Some(path);
+                }
+                "--headless" => options.headless = true,
+                "--ticks" => {
+                    let ticks = value()?;
+                    match ticks.parse() {
+                        Ok(0) | Err(_) => bail!("--ticks needs a positive number, not {ticks}"),
+                        Ok(ticks) => options.ticks = Some(ticks),
+                    }
+                }
+                "--snapshot" => options.snapshot = parse_ticks(&value()?)?,
+                "--export" => options.export = Some(value()?.into()),
+                "--frames" => options.frames = Some(parse_range(&value()?)?),
+                "--scale" => {
+                    let scale = value()?;
+                    match scale.parse() {
+                        Ok(n @ 1..=16) => options.scale = Some(n),
+                        _ => bail!("--scale needs a number from 1 to 16, not {scale}"),
+                    }
+                }
+                "--no-hud" => options.no_hud = true,
+                "--window" => options.window = Some(parse_size(&value()?)?),
+                "--fullscreen" => options.fullscreen = true,
+                "--config" => options.config = Some(value()?.into()),
+                "--demo" => options.demo = true,
+                "--connect" => options.connect = Some(with_port(value()?, net::DEFAULT_PORT)),
+                "--broadcast" | "--spectate" => {
+                    // the address is optional
+                    let addr = args.next_if(|a| !a.starts_with('-'));
+                    let addr = with_port(
+                        addr.unwrap_or_else(|| "127.0.0.1".into()),
+                        broadcast::DEFAULT_PORT,
+                    );
+                    match arg.as_str() {
+                        "--broadcast" => options.broadcast = Some(addr),
+                        _ => options.spectate = Some(addr),
+                    }
+                }
+                "--version" => options.version = true,
+                "-h" | "--help" => options.help = true,
+                _ => bail!("unknown option {arg}"),
+            }
+        }
+        options.check()?;
+        Ok(options)
+    }
+
+    /// Reject options that don't make sense together
+    fn check(&self) -> Result<()> {
+        let exclusive = |option: &str, given: bool, others: &[(&str, bool)]| match others
+            .iter()
+            .find(|(_, other_given)| given && *other_given)
+        {
+            Some((other, _)) => Err(anyhow!("{option} can't be used with {other}")),
+            None => Ok(()),
+        };
+        exclusive("--replay", self.replay.is_some(), &[
+            ("--seed", self.seed.is_some()),
+            ("--course", self.course.is_some()),
+            ("--difficulty", self.difficulty.is_some()),
+            ("--record", self.record.is_some()),
+            ("--demo", self.demo),
+        ])?;
+        exclusive("--connect", self.connect.is_some(), &[
+            ("--seed", self.seed.is_some()),
+            ("--difficulty", self.difficulty.is_some()),
+            ("--replay", self.replay.is_some()),
+        ])?;
+        exclusive("--headless", self.headless, &[
+            ("--record", self.record.is_some()),
+            ("--window", self.window.is_some()),
+            ("--fullscreen", self.fullscreen),
+            ("--connect", self.connect.is_some()),
+            ("--broadcast", self.broadcast.is_some()),
+            ("--spectate", self.spectate.is_some()),
+        ])?;
+        exclusive("--snapshot", !self.snapshot.is_empty(), &[
+            ("--record", self.record.is_some()),
+            ("--fullscreen", self.fullscreen),
+            ("--demo", self.demo),
+            ("--connect", self.connect.is_some()),
+            ("--broadcast", self.broadcast.is_some()),
+            ("--spectate", self.spectate.is_some()),
+        ])?;
+        exclusive("--telemetry", self.telemetry.is_some(), &[
+            ("--spectate", self.spectate.is_some()),
+            ("--export", self.export.is_some()),
+        ])?;
+        exclusive("--export", self.export.is_some(), &[
+            ("--snapshot", !self.snapshot.is_empty()),
+            ("--record", self.record.is_some()),
+            ("--fullscreen", self.fullscreen),
+            ("--connect", self.connect.is_some()),
+            ("--broadcast", self.broadcast.is_some()),
+            ("--spectate", self.spectate.is_some()),
+        ])?;
+        if self.export.is_some() && self.replay.is_none() {
+            bail!("--export needs a --replay to export");
+        }
+        for (option, given) in [
+            ("--frames", self.frames.is_some()),
+            ("--scale", self.scale.is_some()),
+            ("--no-hud", self.no_hud),
+        ] {
+            if given && self.export.is_none() {
+                bail!("{option} is for --export");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `WxH` in pixels
+fn parse_size(size: &str) -> Result<(f32, f32)> {
+    let invalid = || anyhow!("invalid window size {size}, expected WxH like 480x640");
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let width: u16 = width.parse().map_err(|_| invalid())?;
+    let height: u16 = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        bail!(invalid());
+    }
+    Ok((width as f32, height as f32))
+}
+
+/// `A..B`, ticks from A up to B
+fn parse_range(range: &str) -> Result<Range<u32>> {
+    let invalid = || anyhow!("invalid range {range}, expected A..B like 60..600");
+    let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+    let start = start.parse().map_err(|_| invalid())?;
+    let end = end.parse().map_err(|_| invalid())?;
+    if start >= end {
+        bail!("the range {range} has no ticks in it");
+    }
+    Ok(start..end)
+}
+
+/// `tick=N,N,...`, in order
+fn parse_ticks(ticks: &str) -> Result<Vec<u32>> {
+    let invalid = || anyhow!("invalid snapshot {ticks}, expected tick=N or tick=N,N,...");
+    let list = ticks.strip_prefix("tick=").ok_or_else(invalid)?;
+    let mut ticks = list
+        .split(',')
+        .map(|t| t.parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<u32>>>()?;
+    ticks.sort_unstable();
+    ticks.dedup();
+    Ok(ticks)
+}
+
+/// The address, with `port` if it doesn't have one
+fn with_port(addr: String, port: u16) -> String {
+    if addr.contains(':') {
+        addr
+    } else {
+        format!("{addr}:{port}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options =
+            parse("--seed 42 --course tree_slalom --difficulty hard --window 960x1280 --broadcast")
+                .unwrap();
+        assert_eq!(options, Options {
+            seed: Some(42),
+            course: Some(Course::TreeSlalom),
+            difficulty: Some(Difficulty::Hard),
+            window: Some((960., 1280.)),
+            broadcast: Some("127.0.0.1:7879".into()),
+            ..Default::default()
+        });
+        let options = parse("--snapshot tick=600,60 --seed 1 --window 240x320").unwrap();
+        assert_eq!(options.snapshot, [60, 600]);
+        let options =
+            parse("--replay run.toml --export run.gif --frames 60..600 --scale 2 --no-hud")
+                .unwrap();
+        assert_eq!(options.frames, Some(60..600));
+        assert_eq!(options.scale, Some(2));
+        assert!(options.no_hud);
+        let options = parse("--spectate example.com --connect 10.0.0.1").unwrap();
+        assert_eq!(options.spectate.as_deref(), Some("example.com:7879"));
+        assert_eq!(options.connect.as_deref(), Some("10.0.0.1:7878"));
+    }
+
+    #[test]
+    fn bad_arguments_are_explained() {
+        let error = |args| parse(args).unwrap_err().to_string();
+        assert_eq!(error("--seed"), "--seed needs a value");
+        assert_eq!(error("--seed x"), "invalid seed x");
+        assert_eq!(error("--ticks 0"), "--ticks needs a positive number, not 0");
+        assert_eq!(
+            error("--window 480"),
+            "invalid window size 480, expected WxH like 480x640"
+        );
+        assert_eq!(
+            error("--course moguls"),
+            "unknown course moguls, expected slalom, freestyle or tree_slalom"
+        );
+        assert_eq!(
+            error("--snapshot 60"),
+            "invalid snapshot 60, expected tick=N or tick=N,N,..."
+        );
+        assert_eq!(
+            error("--export run.gif"),
+            "--export needs a --replay to export"
+        );
+        assert_eq!(error("--no-hud"), "--no-hud is for --export");
+        assert_eq!(
+            error("--replay run.toml --export run.gif --frames 60..60"),
+            "the range 60..60 has no ticks in it"
+        );
+        assert_eq!(
+            error("--telemetry run.txt"),
+            "telemetry is saved as .csv or .jsonl, not run.txt"
+        );
+        assert_eq!(error("--fast"), "unknown option --fast");
+        assert_eq!(
+            error("--replay run.toml --seed 1"),
+            "--replay can't be used with --seed"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context as _, Result};
+use ggez::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{Bindings, Input};
+use crate::settings::Settings;
+
+/// User configuration, stored as TOML in the user's config directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub settings: Settings,
+    pub bindings: Bindings,
+    /// Bindings for the second skier in a two player race
+    pub player_two: Bindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            settings: Default::default(),
+            bindings: Default::default(),
+            player_two: Bindings::player_two(),
+        }
+    }
+}
+
+impl Config {
+    const FILE_NAME: &'static str = "config.toml";
+
+    pub fn path(ctx: &Context) -> PathBuf {
+        ctx.fs.user_config_dir().join(Self::FILE_NAME)
+    }
+
+    /// Load the config at `path`. A missing file gives the default config,
+    /// and an empty view or a number of players the game can't have is an
+    /// error.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut config: Self = load_toml(path)?;
+        ensure!(
+            config.settings.view_width > 0 && config.settings.view_height > 0,
+            "{}: view_width and view_height must be above 0",
+            path.display()
+        );
+        ensure!(
+            (1..=Settings::MAX_PLAYERS).contains(&config.settings.players),
+            "{}: players must be 1 to {}",
+            path.display(),
+            Settings::MAX_PLAYERS
+        );
+        config.bindings = config.bindings.with_defaults(Bindings::default());
+        config.player_two = config.player_two.with_defaults(Bindings::player_two());
+        Ok(config)
+    }
+
+    /// Bindings of the given skier, counting from 0
+    pub fn bindings(&self, skier: usize) -> &Bindings {
+        if skier == 0 {
+            &self.bindings
+        } else {
+            &self.player_two
+        }
+    }
+
+    /// Inputs that would steer both skiers at once in a two player race
+    pub fn shared_inputs(&self) -> Vec<Input> {
+        self.bindings.shared_with(&self.player_two)
+    }
+
+    pub fn bindings_mut(&mut self, skier: usize) -> &mut Bindings {
+        if skier == 0 {
+            &mut self.bindings
+        } else {
+            &mut self.player_two
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        save_toml(self, path)
+    }
+}
+
+/// Read the TOML file at `path`, or the default when there's no file yet
+pub fn load_toml<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Write `value` to `path` as TOML, making the directory it goes in if need be
+pub fn save_toml<T: Serialize>(value: &T, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let contents = toml::to_string_pretty(value)?;
+    std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Action, Input};
+    use crate::settings::Difficulty;
+
+    #[test]
+    fn can_round_trip_config() {
+        let mut config = Config::default();
+        config.settings.difficulty = Difficulty::Hard;
+        let a = Input::Key(ggez::winit::event::VirtualKeyCode::A);
+        config.bindings.bind(Action::SteerLeft, a);
+        let toml = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn unknown_keys_are_an_error() {
+        let toml = "[bindings]\nflip = [\"NotAKey\"]\n";
+        assert!(toml::from_str::<Config>(toml).is_err());
+    }
+
+    fn load(name: &str, toml: &str) -> Result<Config> {
+        let path = std::env::temp_dir().join(format!("skifree-{name}-{}.toml", std::process::id()));
+        std::fs::write(&path, toml).unwrap();
+        let loaded = Config::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn empty_view_is_an_error() {
+        assert!(load("view", "[settings]\nview_width = 0\n").is_err());
+    }
+
+    #[test]
+    fn players_must_fit_on_the_screen() {
+        assert!(load("no-players", "[settings]\nplayers = 0\n").is_err());
+        assert!(load("many-players", "[settings]\nplayers = 3\n").is_err());
+        assert!(load("two-players", "[settings]\nplayers = 2\n").is_ok());
+    }
+}
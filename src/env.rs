@@ -0,0 +1,333 @@
+//! The game as an environment for reinforcement learning: reset to start an
+//! episode on a slope, then step with one control at a time.
+
+use ggez::graphics::Rect;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::events::{GameEvent, Subscriber};
+use crate::headless::Simulation;
+use crate::map::wrap_x;
+use crate::player::{CollisionAction, Pose, TrickType};
+use crate::score::Score;
+use crate::settings::Difficulty;
+
+/// Columns of cells in the observation grid, centred on the skier
+pub const GRID_COLS: usize = 11;
+/// Rows of cells, from a little above the skier to well below
+pub const GRID_ROWS: usize = 11;
+/// Rows above the skier's row
+const GRID_ROWS_ABOVE: usize = 2;
+/// Width and height of a cell in pixels
+pub const CELL_SIZE: f32 = 24.;
+
+/// What's in a cell of the observation grid
+pub mod cell {
+    pub const EMPTY: u8 = 0;
+    /// Something the skier falls over
+    pub const OBSTACLE: u8 = 1;
+    pub const SMALL_JUMP: u8 = 2;
+    pub const LARGE_JUMP: u8 = 3;
+    pub const CHAIRLIFT: u8 = 4;
+}
+
+/// One of the controls a player has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Control {
+    Nothing,
+    Left,
+    Right,
+    Trick1,
+    Trick2,
+    Flip,
+    /// Boost for this step
+    Boost,
+}
+
+impl Control {
+    pub const ALL: [Control; 7] = [
+        Control::Nothing,
+        Control::Left,
+        Control::Right,
+        Control::Trick1,
+        Control::Trick2,
+        Control::Flip,
+        Control::Boost,
+    ];
+}
+
+/// Reward for each step
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rewards {
+    /// Per step of distance down the mountain
+    pub distance: f32,
+    /// Per style point from landing tricks
+    pub style: f32,
+    /// Per fall, usually negative
+    pub fall: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            distance: 0.1,
+            style: 0.1,
+            fall: -10.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    pub difficulty: Difficulty,
+    /// Episodes end after this many steps, if not before
+    pub max_steps: u32,
+    pub rewards: Rewards,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Normal,
+            max_steps: 5000,
+            rewards: Default::default(),
+        }
+    }
+}
+
+/// What the agent sees
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    /// `GRID_ROWS` rows of `GRID_COLS` cells around the skier, top row first,
+    /// holding one of the `cell` values
+    pub grid: Vec<u8>,
+    pub pose: Pose,
+    /// Current speed in pixels per step, zero when stopped
+    pub speed: f32,
+    pub distance: f32,
+    /// Frames left in the air, while jumping
+    pub jump_frames: Option<i8>,
+    pub style: i32,
+}
+
+/// What happened on a step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    /// The episode is over: finished, eaten or out of steps
+    pub done: bool,
+}
+
+pub struct Env {
+    config: EnvConfig,
+    sim: Simulation,
+    score: Score,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        Self {
+            config,
+            sim: Simulation::new(OsRng.gen(), config.difficulty),
+            score: Default::default(),
+        }
+    }
+
+    /// Start an episode on the slope with `seed`, or a random one
+    pub fn reset(&mut self, seed: Option<u64>) -> Observation {
+        let seed = seed.unwrap_or_else(|| OsRng.gen());
+        self.sim = Simulation::new(seed, self.config.difficulty);
+        self.score = Default::default();
+        self.observe()
+    }
+
+    pub fn step(&mut self, control: Control) -> Step {
+        let player = &mut self.sim.skier.player;
+        player.boost(control == Control::Boost);
+        match control {
+            Control::Left => player.left(),
+            Control::Right => player.right(),
+            Control::Trick1 => player.do_trick(TrickType::Trick1),
+            Control::Trick2 => player.do_trick(TrickType::Trick2),
+            Control::Flip => player.do_trick(TrickType::Flip),
+            Control::Nothing | Control::Boost => {}
+        }
+
+        let distance = player.distance();
+        let style = self.score.style;
+        let mut falls = 0;
+        for event in self.sim.step() {
+            self.score.on_event(&event);
+            if event == GameEvent::Fell {
+                falls += 1;
+            }
+        }
+        let rewards = &self.config.rewards;
+        let reward = (self.sim.skier.player.distance() - distance) * rewards.distance
+            + (self.score.style - style) as f32 * rewards.style
+            + falls as f32 * rewards.fall;
+        Step {
+            observation: self.observe(),
+            reward,
+            done: self.sim.is_over() || self.sim.tick() >= self.config.max_steps,
+        }
+    }
+
+    fn observe(&self) -> Observation {
+        let player = &self.sim.skier.player;
+        Observation {
+            grid: self.grid(),
+            pose: player.pose(),
+            speed: player.current_speed(),
+            distance: player.distance(),
+            jump_frames: player.jump_frame(),
+            style: self.score.style,
+        }
+    }
+
+    /// Objects around the skier's feet, by cell
+    fn grid(&self) -> Vec<u8> {
+        let feet = self.sim.skier.player.hitbox();
+        let area = Rect::new(
+            feet.center().x - GRID_COLS as f32 * CELL_SIZE / 2.,
+            feet.y - GRID_ROWS_ABOVE as f32 * CELL_SIZE,
+            GRID_COLS as f32 * CELL_SIZE,
+            GRID_ROWS as f32 * CELL_SIZE,
+        );
+        let mut grid = vec![cell::EMPTY; GRID_COLS * GRID_ROWS];
+        for o in self.sim.map.objects() {
+            let value = match o.collision_action {
+                CollisionAction::Nothing => continue,
+                CollisionAction::Fall
+                | CollisionAction::Glance
+                | CollisionAction::Stumble
+                | CollisionAction::Deflect => cell::OBSTACLE,
+                CollisionAction::JumpSmall | CollisionAction::Hop => cell::SMALL_JUMP,
+                CollisionAction::JumpLarge => cell::LARGE_JUMP,
+                CollisionAction::Ride => cell::CHAIRLIFT,
+            };
+            let mut hitbox = o.hitbox();
+            hitbox.x = wrap_x(hitbox.x, area.x);
+            if !hitbox.overlaps(&area) {
+                continue;
+            }
+            // every cell the hitbox covers
+            let col = |x: f32| ((x - area.x) / CELL_SIZE).clamp(0., GRID_COLS as f32 - 1.) as usize;
+            let row = |y: f32| ((y - area.y) / CELL_SIZE).clamp(0., GRID_ROWS as f32 - 1.) as usize;
+            for r in row(hitbox.top())..=row(hitbox.bottom()) {
+                for c in col(hitbox.left())..=col(hitbox.right()) {
+                    let cell = &mut grid[r * GRID_COLS + c];
+                    *cell = (*cell).max(value);
+                }
+            }
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::glam::Vec2;
+
+    use super::*;
+    use crate::map::objects::ObjectKind;
+
+    #[test]
+    fn episode_is_repeatable() {
+        let mut env = Env::new(EnvConfig {
+            max_steps: 300,
+            ..Default::default()
+        });
+        let run = |env: &mut Env| {
+            let first = env.reset(Some(7));
+            let mut steps = vec![];
+            for i in 0.. {
+                let step = env.step(Control::ALL[i % Control::ALL.len()]);
+                let done = step.done;
+                steps.push(step);
+                if done {
+                    break;
+                }
+            }
+            (first, steps)
+        };
+        let (first, steps) = run(&mut env);
+        assert_eq!(first.grid.len(), GRID_COLS * GRID_ROWS);
+        assert_eq!(steps.len(), 300);
+        assert_eq!(run(&mut env), (first, steps));
+    }
+
+    #[test]
+    fn rewards_distance_and_punishes_falls() {
+        let mut env = Env::new(Default::default());
+        env.reset(Some(1));
+        // from standing facing right, turn downhill
+        env.step(Control::Left);
+        env.step(Control::Left);
+        env.step(Control::Left);
+        let step = env.step(Control::Nothing);
+        assert_eq!(step.observation.pose, Pose::Down);
+        assert!(step.reward > 0.);
+
+        let player = &mut env.sim.skier.player;
+        let hitbox = player.hitbox();
+        player.collision(ObjectKind::Tree, CollisionAction::Fall, hitbox);
+        let step = env.step(Control::Nothing);
+        assert_eq!(step.observation.pose, Pose::Fallen);
+        assert!(step.reward <= env.config.rewards.fall);
+    }
+
+    #[test]
+    fn grid_shows_whats_around() {
+        let mut env = Env::new(Default::default());
+        let mut grids = vec![env.reset(Some(1)).grid];
+        let start = env.sim.skier.player.position();
+        let mut objects_seen = 0;
+        for tick in 0..600 {
+            // turn downhill, then weave from side to side
+            let control = match tick {
+                0..=2 => Control::Left,
+                _ => match (tick - 3) % 200 {
+                    0 | 150 => Control::Left,
+                    50 | 100 => Control::Right,
+                    _ => Control::Nothing,
+                },
+            };
+            let grid = env.step(control).observation.grid;
+
+            // the cell under the middle of each object in the grid shows it
+            let feet = env.sim.skier.player.hitbox();
+            let corner = Vec2::new(
+                feet.center().x - GRID_COLS as f32 * CELL_SIZE / 2.,
+                feet.y - GRID_ROWS_ABOVE as f32 * CELL_SIZE,
+            );
+            for o in env.sim.map.objects() {
+                if o.collision_action == CollisionAction::Nothing {
+                    continue;
+                }
+                let middle = Vec2::from(o.hitbox().center());
+                let middle = Vec2::new(wrap_x(middle.x, feet.center().x), middle.y);
+                let cell = ((middle - corner) / CELL_SIZE).floor();
+                if cell.x < 0. || cell.y < 0. {
+                    continue;
+                }
+                let (col, row) = (cell.x as usize, cell.y as usize);
+                if col < GRID_COLS && row < GRID_ROWS {
+                    assert_ne!(grid[row * GRID_COLS + col], cell::EMPTY, "{:?}", o.kind);
+                    objects_seen += 1;
+                }
+            }
+            grids.push(grid);
+        }
+        assert!(objects_seen > 0);
+        let moved = env.sim.skier.player.position() - start;
+        assert!(moved.x.abs() > 50. && moved.y > 200., "{moved}");
+        grids.dedup();
+        assert!(grids.len() > 20, "only {} different grids", grids.len());
+    }
+}
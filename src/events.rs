@@ -0,0 +1,64 @@
+use std::vec::Drain;
+
+use crate::achievements::Achievement;
+use crate::map::objects::ObjectKind;
+use crate::map::Course;
+use crate::player::{CollisionAction, TrickType};
+
+/// Something that happened in the simulation that other parts of the game
+/// may want to react to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    Turned,
+    /// The skier ran into an object while upright
+    Collided(ObjectKind, CollisionAction),
+    Jumped {
+        large: bool,
+    },
+    /// Bounced over a mogul. Lands like a jump but isn't counted as one.
+    Hopped,
+    Landed,
+    TrickStarted(TrickType),
+    TrickSucceeded(TrickType),
+    TrickFailed(TrickType),
+    Fell,
+    CourseEntered(Course),
+    GatePassed,
+    GateMissed,
+    /// Crossed the finish line. Only the slalom has gates to miss.
+    CourseFinished {
+        course: Course,
+        missed_gates: u32,
+    },
+    /// Distance travelled down the mountain, in steps of
+    /// `map::MILESTONE_DISTANCE`
+    DistanceMilestone(u32),
+    BoardedLift,
+    LiftTopReached,
+    YetiAppeared,
+    YetiEscaped,
+    YetiCaught,
+    AchievementUnlocked(Achievement),
+}
+
+/// Queue of events emitted during a tick. The simulation only pushes to the
+/// bus, listeners drain it afterwards.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    queue: Vec<GameEvent>,
+}
+
+impl EventBus {
+    pub fn emit(&mut self, event: GameEvent) {
+        self.queue.push(event);
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, GameEvent> {
+        self.queue.drain(..)
+    }
+}
+
+/// Anything that reacts to the events of a tick
+pub trait Subscriber {
+    fn on_event(&mut self, event: &GameEvent);
+}
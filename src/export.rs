@@ -0,0 +1,162 @@
+//! Turning replays into animated GIFs or numbered PNGs, drawn on the CPU
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{Delay, Frame, RgbaImage};
+
+use crate::headless::TICKS_PER_SECOND;
+use crate::replay::Replay;
+use crate::snapshot::{self, Renderer};
+
+/// A GIF gets every third tick, 20 frames a second, as most viewers don't
+/// show frames shorter than 20ms
+const GIF_TICKS: u32 = 3;
+/// How long a run is simulated when the range has no end, ten minutes
+const MAX_TICKS: u32 = 36_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    /// Ticks to export. Stops early when the run is over.
+    pub ticks: Range<u32>,
+    /// Size of the view, before scaling
+    pub width: u32,
+    pub height: u32,
+    /// Every pixel becomes `scale` by `scale` pixels
+    pub scale: u32,
+    pub show_hud: bool,
+}
+
+impl Default for Export {
+    fn default() -> Self {
+        Self {
+            ticks: 0..MAX_TICKS,
+            width: 480,
+            height: 640,
+            scale: 1,
+            show_hud: true,
+        }
+    }
+}
+
+impl Export {
+    /// Simulate the replay again and save its frames to `path`: an animated
+    /// GIF if it ends in `.gif`, otherwise a directory of PNGs named after
+    /// their tick. Returns how many frames were saved.
+    pub fn run(&self, replay: &Replay, path: &Path) -> Result<u32> {
+        if self.ticks.is_empty() {
+            bail!("no ticks to export in {:?}", self.ticks);
+        }
+        let renderer = Renderer::new()?.show_hud(self.show_hud);
+        let gif = path.extension().is_some_and(|e| e == "gif");
+        let mut output = if gif {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let file =
+                File::create(path).with_context(|| format!("creating {}", path.display()))?;
+            let mut encoder = GifEncoder::new(BufWriter::new(file));
+            encoder.set_repeat(Repeat::Infinite)?;
+            Output::Gif(encoder)
+        } else {
+            std::fs::create_dir_all(path)
+                .with_context(|| format!("creating {}", path.display()))?;
+            Output::Pngs(path.to_path_buf())
+        };
+
+        let every = if gif { GIF_TICKS } else { 1 };
+        let mut next = self.ticks.start;
+        let mut sim = replay.start();
+        let mut frames = 0;
+        loop {
+            let tick = sim.tick();
+            if tick == next {
+                let frame =
+                    renderer.render(sim.assets(), &sim.map, &sim.skier, self.width, self.height);
+                output.add(self.scaled(frame), tick)?;
+                frames += 1;
+                next += every;
+            }
+            if tick + 1 >= self.ticks.end || sim.is_over() {
+                break;
+            }
+            replay.step(&mut sim);
+        }
+        Ok(frames)
+    }
+
+    fn scaled(&self, frame: RgbaImage) -> RgbaImage {
+        if self.scale <= 1 {
+            return frame;
+        }
+        let (width, height) = (frame.width() * self.scale, frame.height() * self.scale);
+        image::imageops::resize(&frame, width, height, FilterType::Nearest)
+    }
+}
+
+enum Output {
+    Gif(GifEncoder<BufWriter<File>>),
+    /// Directory of PNGs
+    Pngs(PathBuf),
+}
+
+impl Output {
+    fn add(&mut self, frame: RgbaImage, tick: u32) -> Result<()> {
+        match self {
+            Output::Gif(encoder) => {
+                let delay = Delay::from_numer_denom_ms(GIF_TICKS * 1000, TICKS_PER_SECOND);
+                encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+            }
+            Output::Pngs(dir) => snapshot::save(&frame, &dir.join(format!("{tick:05}.png")))?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    use super::*;
+    use crate::player::TrickType;
+    use crate::replay::PlayerInput;
+    use crate::settings::Difficulty;
+
+    #[test]
+    fn exports_a_range_of_ticks() {
+        let mut replay = Replay::new(5, Difficulty::Normal, None);
+        replay.record(10, PlayerInput::Right);
+        replay.record(40, PlayerInput::Trick {
+            trick: TrickType::Flip,
+        });
+        let export = Export {
+            ticks: 30..60,
+            width: 120,
+            height: 160,
+            scale: 2,
+            show_hud: false,
+        };
+        let dir = std::env::temp_dir().join(format!("skifree-export-{}", std::process::id()));
+
+        let gif = dir.join("flip.gif");
+        assert_eq!(export.run(&replay, &gif).unwrap(), 10);
+        let decoder = GifDecoder::new(File::open(&gif).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[0].buffer().dimensions(), (240, 320));
+
+        let pngs = dir.join("flip");
+        assert_eq!(export.run(&replay, &pngs).unwrap(), 30);
+        let first = image::open(pngs.join("00030.png")).unwrap();
+        assert_eq!((first.width(), first.height()), (240, 320));
+        assert!(pngs.join("00059.png").exists());
+        assert!(!pngs.join("00060.png").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawParam};
+use ggez::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::assets::Assets;
+use crate::events::{GameEvent, Subscriber};
+use crate::map::Course;
+use crate::player::Pose;
+use crate::settings::Difficulty;
+
+/// Where the skier was on the map on one tick
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GhostFrame {
+    pub x: f32,
+    pub y: f32,
+    pub pose: Pose,
+}
+
+/// A run from the start to the finish line, one frame per tick
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub frames: Vec<GhostFrame>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string(self)?;
+        std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Ticks it took to get `y` down the map
+    fn ticks_to(&self, y: f32) -> Option<usize> {
+        self.frames.iter().position(|f| f.y >= y)
+    }
+}
+
+/// What a run is timed over: the slope, and the course it started at, or
+/// the top of the slope. Runs are only raced against runs on the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slope {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub start: Option<Course>,
+}
+
+/// Records the current run and plays back the best run on each course of
+/// the slope
+#[derive(Debug)]
+pub struct Ghosts {
+    dir: PathBuf,
+    /// None until the first run starts
+    slope: Option<Slope>,
+    best: HashMap<Course, Recording>,
+    run: Recording,
+    /// Courses with a new best run that hasn't been saved yet
+    new_best: Vec<Course>,
+}
+
+impl Ghosts {
+    const ALPHA: f32 = 0.4;
+
+    pub fn dir(ctx: &Context) -> PathBuf {
+        ctx.fs.user_data_dir().join("ghosts")
+    }
+
+    /// Ghosts saved in and loaded from `dir`
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            slope: None,
+            best: HashMap::new(),
+            run: Default::default(),
+            new_best: vec![],
+        }
+    }
+
+    fn path(dir: &Path, slope: Slope, course: Course) -> PathBuf {
+        let Slope {
+            seed,
+            difficulty,
+            start,
+        } = slope;
+        let start = start.map_or("top", |c| c.id());
+        dir.join(format!(
+            "{seed:016x}-{}-{start}-{}.toml",
+            difficulty.name(),
+            course.id()
+        ))
+    }
+
+    /// Start recording a new run on `slope`, loading the best runs on it
+    /// if it's not the one the last run was on
+    pub fn restart(&mut self, slope: Slope) {
+        self.run = Default::default();
+        if self.slope == Some(slope) {
+            return;
+        }
+        self.slope = Some(slope);
+        self.best.clear();
+        self.new_best.clear();
+        for course in Course::ALL {
+            let path = Self::path(&self.dir, slope, course);
+            if !path.exists() {
+                continue;
+            }
+            match Recording::load(&path) {
+                Ok(recording) => {
+                    self.best.insert(course, recording);
+                }
+                Err(e) => eprintln!("error loading ghost: {e:#}"),
+            }
+        }
+    }
+
+    pub fn record(&mut self, position: Vec2, pose: Pose) {
+        self.run.frames.push(GhostFrame {
+            x: position.x,
+            y: position.y,
+            pose,
+        });
+    }
+
+    /// Write new best runs to disk
+    pub fn save(&mut self) {
+        let Some(slope) = self.slope else {
+            return;
+        };
+        for course in self.new_best.drain(..) {
+            let path = Self::path(&self.dir, slope, course);
+            if let Err(e) = self.best[&course].save(&path) {
+                eprintln!("error saving ghost: {e:#}");
+            }
+        }
+    }
+
+    /// How many ticks the player is behind (positive) or ahead (negative)
+    /// of the ghost at the player's position
+    pub fn delta(&self, course: Option<Course>, position: Vec2) -> Option<i64> {
+        let ghost = self.best.get(&course?)?;
+        let ghost_ticks = ghost.ticks_to(position.y)?;
+        Some(self.run.frames.len() as i64 - ghost_ticks as i64)
+    }
+
+    /// Draw the ghost where it was at this point of its run. `offset` moves
+    /// map coordinates to screen coordinates.
+    pub fn draw(&self, canvas: &mut Canvas, assets: &Assets, course: Option<Course>, offset: Vec2) {
+        let Some(ghost) = course.and_then(|c| self.best.get(&c)) else {
+            return;
+        };
+        let tick = self.run.frames.len();
+        let Some(frame) = ghost.frames.get(tick).or(ghost.frames.last()) else {
+            return;
+        };
+        canvas.draw(
+            frame.pose.image(assets).as_ref(),
+            DrawParam::default()
+                .dest(Vec2::new(frame.x, frame.y) + offset)
+                .color(Color::new(1., 1., 1., Self::ALPHA)),
+        );
+    }
+}
+
+impl Subscriber for Ghosts {
+    fn on_event(&mut self, event: &GameEvent) {
+        if let GameEvent::CourseFinished { course, .. } = *event {
+            let is_best = match self.best.get(&course) {
+                Some(best) => self.run.frames.len() < best.frames.len(),
+                None => true,
+            };
+            if is_best {
+                self.best.insert(course, self.run.clone());
+                self.new_best.push(course);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(ys: &[f32]) -> Recording {
+        Recording {
+            frames: ys
+                .iter()
+                .map(|&y| GhostFrame {
+                    x: 0.,
+                    y,
+                    pose: Pose::Down,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn keeps_fastest_run_and_measures_delta() {
+        let mut ghosts = Ghosts::new(PathBuf::from("/nonexistent"));
+        ghosts.restart(Slope {
+            seed: 1,
+            difficulty: Difficulty::Normal,
+            start: None,
+        });
+        let finish = GameEvent::CourseFinished {
+            course: Course::Freestyle,
+            missed_gates: 0,
+        };
+        ghosts.run = recording(&[0., 1., 2., 3.]);
+        ghosts.on_event(&finish);
+        ghosts.run = recording(&[0., 2., 4.]);
+        ghosts.on_event(&finish);
+        ghosts.run = recording(&[0., 0., 1., 1., 2.]);
+        ghosts.on_event(&finish);
+        assert_eq!(ghosts.best[&Course::Freestyle], recording(&[0., 2., 4.]));
+
+        // 5 ticks into the run, where the ghost was after 1
+        let delta = ghosts.delta(Some(Course::Freestyle), Vec2::new(0., 2.));
+        assert_eq!(delta, Some(4));
+        assert_eq!(ghosts.delta(Some(Course::Slalom), Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn races_only_runs_on_the_same_slope_and_start() {
+        let dir = std::env::temp_dir().join(format!("skifree-ghosts-{}", std::process::id()));
+        let slope = Slope {
+            seed: 1,
+            difficulty: Difficulty::Hard,
+            start: None,
+        };
+        let mut ghosts = Ghosts::new(dir.clone());
+        ghosts.restart(slope);
+        ghosts.run = recording(&[0., 1., 2.]);
+        ghosts.on_event(&GameEvent::CourseFinished {
+            course: Course::Freestyle,
+            missed_gates: 0,
+        });
+        ghosts.save();
+
+        let mut ghosts = Ghosts::new(dir.clone());
+        ghosts.restart(slope);
+        assert!(ghosts.best.contains_key(&Course::Freestyle));
+        for other in [
+            Slope {
+                difficulty: Difficulty::Easy,
+                ..slope
+            },
+            Slope {
+                start: Some(Course::Freestyle),
+                ..slope
+            },
+        ] {
+            ghosts.restart(other);
+            assert!(ghosts.best.is_empty(), "{other:?}");
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn can_round_trip_recording() {
+        let recording = recording(&[0., 1.5]);
+        let toml = toml::to_string(&recording).unwrap();
+        assert_eq!(toml::from_str::<Recording>(&toml).unwrap(), recording);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::assets::Assets;
+use crate::events::{GameEvent, Subscriber};
+use crate::map::Map;
+use crate::settings::Difficulty;
+use crate::skier::Skier;
+
+/// Ticks in a second of the game, with a window or without, and on the
+/// server
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// One skier on a slope, simulated without a window, e.g. for tests and
+/// bots
+pub struct Simulation {
+    assets: Assets,
+    pub map: Map,
+    pub skier: Skier,
+    tick: u32,
+}
+
+impl Simulation {
+    pub fn new(seed: u64, difficulty: Difficulty) -> Self {
+        let assets = Assets::headless();
+        Self {
+            map: Map::new(&assets, difficulty, seed),
+            skier: Skier::new(&assets, false),
+            assets,
+            tick: 0,
+        }
+    }
+
+    pub fn assets(&self) -> &Assets {
+        &self.assets
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Simulate a tick, returning what happened. The skier's score, HUD and
+    /// stats keep up as they do in the game.
+    pub fn step(&mut self) -> Vec<GameEvent> {
+        self.tick += 1;
+        self.skier.update(0, &mut self.map, &self.assets);
+        self.map.update();
+
+        let skier = &mut self.skier;
+        let events: Vec<_> = skier.player.events().drain().collect();
+        for event in &events {
+            skier.score.on_event(event);
+            skier.hud.on_event(event);
+            skier.stats.on_event(event);
+        }
+        skier.hud = skier
+            .hud
+            .set_distance(skier.player.distance())
+            .set_speed(skier.player.current_speed() * TICKS_PER_SECOND as f32)
+            .set_style(skier.score.style)
+            .set_position(skier.player.position(), skier.progress.course())
+            .add_time(1. / TICKS_PER_SECOND as f32);
+        events
+    }
+
+    /// Finished, or eaten by the yeti
+    pub fn is_over(&self) -> bool {
+        self.skier.is_over()
+    }
+}
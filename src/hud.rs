@@ -1,13 +1,54 @@
-use ggez::graphics::{Canvas, Color, DrawParam, Text};
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, Mesh, PxScale, Rect, Text};
 use ggez::Context;
 
-#[derive(Default, Clone, Copy)]
+use crate::achievements::Achievement;
+use crate::events::{GameEvent, Subscriber};
+use crate::map::{Course, LIFT_X_POS, MAP_HEIGHT, MAP_WIDTH, MAP_X_START};
+
+#[derive(Clone, Copy)]
 pub struct Hud {
     pub distance: f32,
     pub elapsed_time: f32,
-    pub score: i32,
+    /// Pixels a second
+    pub speed: f32,
+    pub style: i32,
+    pub course: Option<Course>,
+    /// Player position on the map, for the minimap
+    pub position: Vec2,
+    pub show_minimap: bool,
+    /// Seconds behind the ghost, negative when ahead
+    pub ghost_delta: Option<f32>,
+    /// Message flashed in the middle of the screen and the seconds it has left
+    pub notice: Option<(&'static str, f32)>,
+    /// Achievement just unlocked and the seconds its toast has left
+    pub toast: Option<(Achievement, f32)>,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            distance: 0.,
+            elapsed_time: 0.,
+            speed: 0.,
+            style: 0,
+            course: None,
+            position: Vec2::ZERO,
+            show_minimap: true,
+            ghost_delta: None,
+            notice: None,
+            toast: None,
+        }
+    }
 }
 
+/// Space between the edge of the window and the HUD
+const MARGIN: f32 = 8.;
+const PADDING: f32 = 6.;
+/// How long a notice stays on screen, in seconds
+const NOTICE_TIME: f32 = 1.5;
+const TOAST_TIME: f32 = 4.;
+
 impl Hud {
     pub fn set_distance(mut self, distance: f32) -> Self {
         self.distance = distance;
@@ -16,17 +57,204 @@ impl Hud {
 
     pub fn add_time(mut self, time: f32) -> Self {
         self.elapsed_time += time;
+        self.notice = self
+            .notice
+            .map(|(notice, left)| (notice, left - time))
+            .filter(|(_, left)| *left > 0.);
+        self.toast = self
+            .toast
+            .map(|(achievement, left)| (achievement, left - time))
+            .filter(|(_, left)| *left > 0.);
         self
     }
 
-    pub fn draw(&self, ctx: &Context, canvas: &mut Canvas) {
-        canvas.draw(
-            &Text::new(format!("distance: {}", self.distance as u32)),
-            DrawParam::new().dest([0., 0.]).color(Color::BLACK),
+    pub fn set_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn set_style(mut self, style: i32) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn set_position(mut self, position: Vec2, course: Option<Course>) -> Self {
+        self.position = position;
+        self.course = course;
+        self
+    }
+
+    pub fn set_ghost_delta(mut self, ghost_delta: Option<f32>) -> Self {
+        self.ghost_delta = ghost_delta;
+        self
+    }
+
+    pub fn set_show_minimap(mut self, show_minimap: bool) -> Self {
+        self.show_minimap = show_minimap;
+        self
+    }
+
+    /// Draw the HUD over `area` of the window, the skier's viewport
+    pub fn draw(&self, ctx: &Context, canvas: &mut Canvas, area: Rect) {
+        let (width, height) = (area.w, area.h);
+        // scale everything off the viewport height so the panel keeps its
+        // proportions when the window changes size
+        let font_size = (height / 40.).clamp(10., 20.);
+        let panel_width = (width * 0.35).clamp(120., 220.);
+
+        let mut text = Text::new(self.lines().join("\n"));
+        text.set_scale(PxScale::from(font_size));
+        let text_height = text.measure(ctx).map_or(font_size * 5., |m| m.y);
+
+        let panel = Rect::new(
+            area.right() - panel_width - MARGIN,
+            area.y + MARGIN,
+            panel_width,
+            text_height + PADDING * 2.,
         );
+        draw_panel(ctx, canvas, panel);
         canvas.draw(
-            &Text::new(format!("elapsed time: {:.2}s", self.elapsed_time)),
-            DrawParam::new().dest([0., 12.]).color(Color::BLACK),
+            &text,
+            DrawParam::new()
+                .dest([panel.x + PADDING, panel.y + PADDING])
+                .color(Color::BLACK),
         );
+
+        if self.show_minimap {
+            let minimap_height = (height * 0.3).min(panel_width * 1.5);
+            let minimap = Rect::new(
+                panel.x,
+                panel.bottom() + MARGIN,
+                panel_width,
+                minimap_height,
+            );
+            self.draw_minimap(ctx, canvas, minimap);
+        }
+
+        if let Some((notice, _)) = self.notice {
+            let mut text = Text::new(notice);
+            text.set_scale(PxScale::from(font_size * 1.5));
+            let size = text.measure(ctx).map_or(Vec2::ZERO, Vec2::from);
+            canvas.draw(
+                &text,
+                DrawParam::new()
+                    .dest([area.x + (width - size.x) / 2., area.y + height / 5.])
+                    .color(Color::BLACK),
+            );
+        }
+
+        if let Some((achievement, _)) = self.toast {
+            let mut text = Text::new(format!(
+                "Achievement unlocked: {}\n{}",
+                achievement.name(),
+                achievement.description()
+            ));
+            text.set_scale(PxScale::from(font_size));
+            let size = text.measure(ctx).map_or(Vec2::ZERO, Vec2::from);
+            let toast = Rect::new(
+                area.x + (width - size.x) / 2. - PADDING,
+                area.bottom() - size.y - PADDING * 2. - MARGIN,
+                size.x + PADDING * 2.,
+                size.y + PADDING * 2.,
+            );
+            draw_panel(ctx, canvas, toast);
+            canvas.draw(
+                &text,
+                DrawParam::new()
+                    .dest([toast.x + PADDING, toast.y + PADDING])
+                    .color(Color::BLACK),
+            );
+        }
+    }
+
+    /// The text of the panel
+    pub fn lines(&self) -> Vec<String> {
+        let minutes = (self.elapsed_time / 60.) as u32;
+        let seconds = self.elapsed_time % 60.;
+        let mut lines = vec![
+            format!("Time:  {minutes}:{seconds:05.2}"),
+            format!("Dist:  {}", self.distance as u32),
+            format!("Speed: {:.0}px/s", self.speed),
+            format!("Style: {}", self.style),
+            format!("Course: {}", self.course.map_or("-", |c| c.name())),
+        ];
+        if let Some(delta) = self.ghost_delta {
+            lines.push(format!("Ghost: {delta:+.2}s"));
+        }
+        lines
+    }
+
+    fn draw_minimap(&self, ctx: &Context, canvas: &mut Canvas, area: Rect) {
+        draw_panel(ctx, canvas, area);
+
+        // map coordinates to minimap coordinates
+        let map_width = (MAP_WIDTH - MAP_X_START) as f32;
+        let to_minimap = |x: f32, y: f32| {
+            Vec2::new(
+                area.x + (x - MAP_X_START as f32) / map_width * area.w,
+                area.y + (y / MAP_HEIGHT as f32).clamp(0., 1.) * area.h,
+            )
+        };
+
+        for course in Course::ALL {
+            let range = course.x_range();
+            let start = to_minimap(range.start as f32, 0.);
+            let end = to_minimap(range.end as f32, MAP_HEIGHT as f32);
+            let color = match course {
+                Course::Slalom => Color::from_rgb(220, 230, 255),
+                Course::Freestyle => Color::from_rgb(255, 235, 220),
+                Course::TreeSlalom => Color::from_rgb(220, 245, 220),
+            };
+            let rect = Rect::new(start.x, start.y, end.x - start.x, end.y - start.y);
+            if let Ok(mesh) = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color) {
+                canvas.draw(&mesh, DrawParam::default());
+            }
+        }
+
+        let lift = [
+            to_minimap(LIFT_X_POS, 0.),
+            to_minimap(LIFT_X_POS, MAP_HEIGHT as f32),
+        ];
+        if let Ok(mesh) = Mesh::new_line(ctx, &lift, 1., Color::from_rgb(120, 120, 120)) {
+            canvas.draw(&mesh, DrawParam::default());
+        }
+
+        let player = to_minimap(self.position.x, self.position.y);
+        if let Ok(mesh) = Mesh::new_circle(ctx, DrawMode::fill(), player, 2.5, 0.5, Color::RED) {
+            canvas.draw(&mesh, DrawParam::default());
+        }
+    }
+}
+
+impl Subscriber for Hud {
+    fn on_event(&mut self, event: &GameEvent) {
+        let notice = match event {
+            GameEvent::TrickSucceeded(_) => "Nice!",
+            GameEvent::TrickFailed(_) => "Wipeout!",
+            GameEvent::GatePassed => "Gate",
+            GameEvent::GateMissed => "Missed gate",
+            GameEvent::CourseEntered(course) => course.name(),
+            GameEvent::YetiAppeared => "Yeti!",
+            GameEvent::YetiCaught => {
+                // stays up until the run is restarted
+                self.notice = Some(("Eaten by the yeti!", f32::INFINITY));
+                return;
+            }
+            GameEvent::AchievementUnlocked(achievement) => {
+                self.toast = Some((*achievement, TOAST_TIME));
+                return;
+            }
+            _ => return,
+        };
+        self.notice = Some((notice, NOTICE_TIME));
+    }
+}
+
+fn draw_panel(ctx: &Context, canvas: &mut Canvas, rect: Rect) {
+    if let Ok(fill) = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::WHITE) {
+        canvas.draw(&fill, DrawParam::default());
+    }
+    if let Ok(border) = Mesh::new_rectangle(ctx, DrawMode::stroke(1.), rect, Color::BLACK) {
+        canvas.draw(&border, DrawParam::default());
     }
 }
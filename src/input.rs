@@ -0,0 +1,582 @@
+use std::collections::BTreeMap;
+
+use ggez::event::{Axis, Button};
+use ggez::glam::Vec2;
+use ggez::winit::event::VirtualKeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::util::angle_from_vec2;
+
+/// Something the player can do, independent of the key that triggers it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    SteerLeft,
+    SteerRight,
+    Trick1,
+    Trick2,
+    Flip,
+    Boost,
+    Pause,
+    Quit,
+    ToggleMinimap,
+    /// Face the way an analog stick points
+    Steer,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::SteerLeft,
+        Action::SteerRight,
+        Action::Trick1,
+        Action::Trick2,
+        Action::Flip,
+        Action::Boost,
+        Action::Pause,
+        Action::Quit,
+        Action::ToggleMinimap,
+        Action::Steer,
+    ];
+
+    /// Name used in the config file
+    pub fn id(&self) -> &'static str {
+        match self {
+            Action::SteerLeft => "steer_left",
+            Action::SteerRight => "steer_right",
+            Action::Trick1 => "trick1",
+            Action::Trick2 => "trick2",
+            Action::Flip => "flip",
+            Action::Boost => "boost",
+            Action::Pause => "pause",
+            Action::Quit => "quit",
+            Action::ToggleMinimap => "toggle_minimap",
+            Action::Steer => "steer",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Action> {
+        Self::ALL.into_iter().find(|a| a.id() == id)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::SteerLeft => "Steer left",
+            Action::SteerRight => "Steer right",
+            Action::Trick1 => "Trick 1",
+            Action::Trick2 => "Trick 2",
+            Action::Flip => "Flip",
+            Action::Boost => "Boost",
+            Action::Pause => "Pause",
+            Action::Quit => "Quit",
+            Action::ToggleMinimap => "Minimap",
+            Action::Steer => "Stick steer",
+        }
+    }
+
+    /// Whether the first skier must keep an input for it, as without one
+    /// there's no way to open the menu or leave the game
+    pub fn is_required(&self) -> bool {
+        matches!(self, Action::Pause | Action::Quit)
+    }
+
+    /// Whether it's bound to an analog stick rather than keys and buttons
+    pub fn is_analog(&self) -> bool {
+        *self == Action::Steer
+    }
+}
+
+/// A key, gamepad button or analog stick that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Button(Button),
+    Stick(Thumbstick),
+}
+
+/// One of the analog sticks of a gamepad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Thumbstick {
+    LeftStick,
+    RightStick,
+}
+
+impl Input {
+    /// Name used in the config file. Gamepad buttons are prefixed with `Pad`.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Input::Key(key) => KEYS.iter().find(|(_, k)| k == key).map(|(name, _)| *name),
+            Input::Button(button) => BUTTONS
+                .iter()
+                .find(|(_, b)| b == button)
+                .map(|(name, _)| *name),
+            Input::Stick(stick) => STICKS
+                .iter()
+                .find(|(_, s)| s == stick)
+                .map(|(name, _)| *name),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Input> {
+        let key = KEYS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, key)| Input::Key(*key));
+        key.or_else(|| {
+            BUTTONS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, button)| Input::Button(*button))
+        })
+        .or_else(|| {
+            STICKS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, stick)| Input::Stick(*stick))
+        })
+    }
+}
+
+/// What the game receives from the keyboard or a gamepad once the input has
+/// been mapped through the bindings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Pressed(Action),
+    Released(Action),
+    /// Steering from an analog stick, as an angle in the same convention as
+    /// [`crate::util::vec2_from_angle`]. `None` when the stick is centered.
+    Steer(Option<f32>),
+}
+
+/// Tracks the analog sticks so they can be turned into steering commands
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stick {
+    left: Vec2,
+    right: Vec2,
+}
+
+impl Stick {
+    /// How far the stick has to be pushed before it steers
+    const DEAD_ZONE: f32 = 0.5;
+
+    /// Update an axis of a stick. Returns the stick and its steering command
+    /// if it moved.
+    pub fn axis(&mut self, axis: Axis, value: f32) -> Option<(Thumbstick, Command)> {
+        // gilrs reports up as positive, the map's y axis points down
+        let (stick, v) = match axis {
+            Axis::LeftStickX => (Thumbstick::LeftStick, &mut self.left.x),
+            Axis::LeftStickY => (Thumbstick::LeftStick, &mut self.left.y),
+            Axis::RightStickX => (Thumbstick::RightStick, &mut self.right.x),
+            Axis::RightStickY => (Thumbstick::RightStick, &mut self.right.y),
+            _ => return None,
+        };
+        *v = match axis {
+            Axis::LeftStickY | Axis::RightStickY => -value,
+            _ => value,
+        };
+        let v = match stick {
+            Thumbstick::LeftStick => self.left,
+            Thumbstick::RightStick => self.right,
+        };
+        Some((
+            stick,
+            Command::Steer((v.length() >= Self::DEAD_ZONE).then(|| angle_from_vec2(v))),
+        ))
+    }
+}
+
+/// Inputs bound to each action. An action can have several inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bindings(#[serde(with = "input_names")] BTreeMap<Action, Vec<Input>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Input::{Button as Pad, Key};
+        use VirtualKeyCode as K;
+        let bindings = [
+            (Action::SteerLeft, vec![Key(K::Left), Pad(Button::DPadLeft)]),
+            (Action::SteerRight, vec![
+                Key(K::Right),
+                Pad(Button::DPadRight),
+            ]),
+            (Action::Trick1, vec![Key(K::Z), Pad(Button::South)]),
+            (Action::Trick2, vec![Key(K::X), Pad(Button::East)]),
+            (Action::Flip, vec![Key(K::C), Pad(Button::North)]),
+            (Action::Boost, vec![Key(K::F), Pad(Button::RightTrigger2)]),
+            (Action::Pause, vec![Key(K::Escape), Pad(Button::Start)]),
+            (Action::Quit, vec![Key(K::Q)]),
+            (Action::ToggleMinimap, vec![Key(K::M), Pad(Button::Select)]),
+            (Action::Steer, vec![Input::Stick(Thumbstick::LeftStick)]),
+        ];
+        Self(BTreeMap::from(bindings))
+    }
+}
+
+impl Bindings {
+    /// Keys for the second skier on a split screen. Everything but skiing is
+    /// left to the first player.
+    pub fn player_two() -> Self {
+        use VirtualKeyCode as K;
+        let key = |k| vec![Input::Key(k)];
+        let bindings = [
+            (Action::SteerLeft, key(K::A)),
+            (Action::SteerRight, key(K::D)),
+            (Action::Trick1, key(K::Key1)),
+            (Action::Trick2, key(K::Key2)),
+            (Action::Flip, key(K::Key3)),
+            (Action::Boost, key(K::LShift)),
+            (Action::Pause, vec![]),
+            (Action::Quit, vec![]),
+            (Action::ToggleMinimap, vec![]),
+            (Action::Steer, vec![]),
+        ];
+        Self(BTreeMap::from(bindings))
+    }
+
+    /// All actions bound to the given input
+    pub fn actions(&self, input: Input) -> impl Iterator<Item = Action> + '_ {
+        self.0
+            .iter()
+            .filter(move |(_, inputs)| inputs.contains(&input))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn inputs(&self, action: Action) -> &[Input] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn bind(&mut self, action: Action, input: Input) {
+        let inputs = self.0.entry(action).or_default();
+        if !inputs.contains(&input) {
+            inputs.push(input);
+        }
+    }
+
+    /// Unbind all inputs from the action
+    pub fn clear(&mut self, action: Action) {
+        self.0.insert(action, vec![]);
+    }
+
+    /// Use the inputs from `defaults` for any action missing from the
+    /// bindings, or required and left with none
+    pub fn with_defaults(mut self, defaults: Self) -> Self {
+        for (action, inputs) in defaults.0 {
+            match self.0.get_mut(&action) {
+                None => {
+                    self.0.insert(action, inputs);
+                }
+                Some(bound) if bound.is_empty() && action.is_required() => *bound = inputs,
+                Some(_) => {}
+            }
+        }
+        self
+    }
+
+    /// Inputs that are bound to more than one action
+    pub fn conflicts(&self) -> Vec<(Input, Vec<Action>)> {
+        let mut conflicts: Vec<(Input, Vec<Action>)> = vec![];
+        for input in self.0.values().flatten() {
+            if conflicts.iter().any(|(i, _)| i == input) {
+                continue;
+            }
+            let actions: Vec<_> = self.actions(*input).collect();
+            if actions.len() > 1 {
+                conflicts.push((*input, actions));
+            }
+        }
+        conflicts
+    }
+
+    /// Inputs bound here that are bound in `other` too
+    pub fn shared_with(&self, other: &Bindings) -> Vec<Input> {
+        let mut shared = vec![];
+        for input in self.0.values().flatten() {
+            if !shared.contains(input) && other.actions(*input).next().is_some() {
+                shared.push(*input);
+            }
+        }
+        shared
+    }
+
+    /// Whether an input is bound to this action and at least one other
+    pub fn is_conflicting(&self, action: Action) -> bool {
+        self.inputs(action)
+            .iter()
+            .any(|input| self.actions(*input).any(|a| a != action))
+    }
+}
+
+macro_rules! input_table {
+    ($table:ident, $ty:ident, $prefix:literal, $($name:ident),* $(,)?) => {
+        const $table: &[(&str, $ty)] = &[$((concat!($prefix, stringify!($name)), $ty::$name)),*];
+    };
+}
+
+// Inputs that can be written in the config file
+input_table!(
+    KEYS,
+    VirtualKeyCode,
+    "",
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Left,
+    Right,
+    Up,
+    Down,
+    Escape,
+    Space,
+    Return,
+    Tab,
+    Back,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    LShift,
+    RShift,
+    LControl,
+    RControl,
+    LAlt,
+    RAlt,
+    Comma,
+    Period,
+    Slash,
+    Semicolon,
+    Apostrophe,
+    Minus,
+    Equals,
+    LBracket,
+    RBracket,
+    Backslash,
+    Grave,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+);
+input_table!(
+    BUTTONS,
+    Button,
+    "Pad",
+    South,
+    East,
+    North,
+    West,
+    C,
+    Z,
+    LeftTrigger,
+    LeftTrigger2,
+    RightTrigger,
+    RightTrigger2,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+);
+input_table!(STICKS, Thumbstick, "Pad", LeftStick, RightStick);
+
+/// (De)serialize inputs by their name, e.g. `"Left"` or `"PadSouth"`
+mod input_names {
+    use std::collections::BTreeMap;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Action, Input};
+
+    pub fn serialize<S: Serializer>(
+        bindings: &BTreeMap<Action, Vec<Input>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bindings
+            .iter()
+            .map(|(action, inputs)| {
+                let inputs = inputs.iter().filter_map(Input::name).collect();
+                (action.id(), inputs)
+            })
+            .collect::<BTreeMap<_, Vec<_>>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Action, Vec<Input>>, D::Error> {
+        BTreeMap::<String, Vec<String>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(id, names)| {
+                let action = Action::from_id(&id)
+                    .ok_or_else(|| D::Error::custom(format!("unknown action `{id}`")))?;
+                let inputs = names
+                    .iter()
+                    .map(|name| {
+                        Input::from_name(name)
+                            .ok_or_else(|| D::Error::custom(format!("unknown input `{name}`")))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok((action, inputs))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_have_no_conflicts() {
+        assert!(Bindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn player_bindings_dont_overlap() {
+        let one = Bindings::default();
+        let two = Bindings::player_two();
+        assert!(two.conflicts().is_empty());
+        assert!(one.shared_with(&two).is_empty());
+
+        let mut two = two;
+        let left = Input::Key(VirtualKeyCode::Left);
+        two.bind(Action::Flip, left);
+        assert_eq!(one.shared_with(&two), vec![left]);
+        assert_eq!(two.shared_with(&one), vec![left]);
+    }
+
+    #[test]
+    fn can_detect_conflicting_bindings() {
+        let mut bindings = Bindings::default();
+        let z = Input::Key(VirtualKeyCode::Z);
+        bindings.bind(Action::Boost, z);
+        assert_eq!(bindings.conflicts(), vec![(z, vec![
+            Action::Trick1,
+            Action::Boost
+        ])]);
+        assert!(bindings.is_conflicting(Action::Trick1));
+        assert!(!bindings.is_conflicting(Action::Flip));
+    }
+
+    #[test]
+    fn can_bind_several_inputs_to_an_action() {
+        let mut bindings = Bindings::default();
+        let a = Input::Key(VirtualKeyCode::A);
+        bindings.bind(Action::SteerLeft, a);
+        let actions: Vec<_> = bindings.actions(a).collect();
+        assert_eq!(actions, vec![Action::SteerLeft]);
+        assert_eq!(bindings.inputs(Action::SteerLeft), &[
+            Input::Key(VirtualKeyCode::Left),
+            Input::Button(Button::DPadLeft),
+            a
+        ]);
+    }
+
+    #[test]
+    fn missing_actions_use_default_inputs() {
+        let mut bindings = Bindings(BTreeMap::new());
+        let v = Input::Key(VirtualKeyCode::V);
+        bindings.bind(Action::Flip, v);
+        bindings.clear(Action::Boost);
+        bindings.clear(Action::Pause);
+        let bindings = bindings.with_defaults(Bindings::default());
+        assert_eq!(bindings.inputs(Action::Flip), &[v]);
+        assert_eq!(bindings.inputs(Action::Boost), &[]);
+        assert_eq!(bindings.inputs(Action::Pause), &[
+            Input::Key(VirtualKeyCode::Escape),
+            Input::Button(Button::Start)
+        ]);
+        assert_eq!(bindings.inputs(Action::Trick1), &[
+            Input::Key(VirtualKeyCode::Z),
+            Input::Button(Button::South)
+        ]);
+    }
+
+    #[test]
+    fn can_convert_input_names() {
+        let left = Input::Key(VirtualKeyCode::Left);
+        let south = Input::Button(Button::South);
+        assert_eq!(Input::from_name("left"), Some(left));
+        assert_eq!(Input::from_name("PadSouth"), Some(south));
+        assert_eq!(south.name(), Some("PadSouth"));
+        assert_eq!(Input::Key(VirtualKeyCode::Key1).name(), Some("Key1"));
+        assert_eq!(
+            Input::from_name("PadRightStick"),
+            Some(Input::Stick(Thumbstick::RightStick))
+        );
+        assert_eq!(Input::from_name("nope"), None);
+    }
+
+    #[test]
+    fn stick_steers_outside_dead_zone() {
+        let mut stick = Stick::default();
+        let left = Thumbstick::LeftStick;
+        assert_eq!(
+            stick.axis(Axis::LeftStickX, 0.2),
+            Some((left, Command::Steer(None)))
+        );
+        assert_eq!(
+            stick.axis(Axis::LeftStickX, 1.),
+            Some((left, Command::Steer(Some(std::f32::consts::FRAC_PI_2))))
+        );
+        assert_eq!(
+            stick.axis(Axis::RightStickY, 0.2),
+            Some((Thumbstick::RightStick, Command::Steer(None)))
+        );
+        assert_eq!(stick.axis(Axis::LeftZ, 1.), None);
+    }
+}
@@ -0,0 +1,34 @@
+//! A SkiFree clone. The game itself is in `main.rs`, the rest is shared with
+//! the multiplayer server.
+
+#![feature(never_type)]
+
+pub mod achievements;
+pub mod animation;
+pub mod assets;
+pub mod audio;
+pub mod bot;
+pub mod cli;
+pub mod config;
+pub mod env;
+pub mod events;
+pub mod export;
+pub mod ghost;
+pub mod headless;
+pub mod hud;
+pub mod input;
+pub mod map;
+pub mod menu;
+pub mod net;
+pub mod player;
+pub mod race;
+pub mod replay;
+pub mod score;
+pub mod settings;
+pub mod skier;
+pub mod snapshot;
+pub mod spectator;
+pub mod stats;
+pub mod telemetry;
+pub mod util;
+pub mod yeti;
@@ -1,85 +1,794 @@
-#![feature(never_type)]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use std::path::PathBuf;
-
-use anyhow::Result;
-use ggez::conf::{WindowMode, WindowSetup};
-use ggez::event::{self, EventHandler};
-use ggez::graphics::{Canvas, Color, Sampler};
+use anyhow::{Context as _, Result};
+use ggez::conf::{FullscreenType, WindowMode, WindowSetup};
+use ggez::event::{self, Axis, Button, EventHandler, GamepadId};
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, Mesh, PxScale, Rect, Sampler, Text};
 use ggez::input::keyboard::KeyInput;
-use ggez::winit::event::VirtualKeyCode;
 use ggez::{Context, ContextBuilder, GameResult};
-use player::TrickType;
-
-use crate::assets::Assets;
-use crate::hud::Hud;
-use crate::map::Map;
-use crate::player::Player;
-
-mod assets;
-mod hud;
-mod map;
-mod player;
-mod util;
+use rand::rngs::OsRng;
+use rand::Rng;
+use skifree_rs::achievements::Achievements;
+use skifree_rs::assets::{self, Assets};
+use skifree_rs::audio::Audio;
+use skifree_rs::bot::Bot;
+use skifree_rs::cli::{Options, USAGE};
+use skifree_rs::config::Config;
+use skifree_rs::events::{GameEvent, Subscriber};
+use skifree_rs::export::Export;
+use skifree_rs::ghost::{Ghosts, Slope};
+use skifree_rs::headless::{Simulation, TICKS_PER_SECOND};
+use skifree_rs::input::{Action, Command, Input, Stick};
+use skifree_rs::map::{wrap_x, Course, Map};
+use skifree_rs::menu::{Menu, MenuEvent};
+use skifree_rs::net::broadcast::{Broadcast, Watch};
+use skifree_rs::net::client::Client;
+use skifree_rs::net::protocol::{RemoteSkier, RunStatus, SpectatedSkier};
+use skifree_rs::player::TrickType;
+use skifree_rs::race::{format_time, Race};
+use skifree_rs::replay::{PlayerInput, Replay};
+use skifree_rs::settings::{Difficulty, Settings, Skin};
+use skifree_rs::skier::Skier;
+use skifree_rs::snapshot::{self, Renderer};
+use skifree_rs::spectator::Spectator;
+use skifree_rs::stats::Totals;
+use skifree_rs::telemetry::Telemetry;
+use skifree_rs::util::{integer_scale, screen_size};
 
+/// Size of the window until the config is loaded
 const WINDOW_WIDTH: f32 = 480.;
 const WINDOW_HEIGHT: f32 = 640.;
 
 struct SkiFree {
     assets: Assets,
+    /// Seed the slope was generated from
+    seed: u64,
+    /// Difficulty from the command line, instead of the one in the settings
+    difficulty: Option<Difficulty>,
+    /// Course the skiers start on, instead of the top of the slope
+    course: Option<Course>,
     map: Map,
-    player: Player,
-    hud: Hud,
+    /// One skier, or two racing on a split screen
+    skiers: Vec<Skier>,
+    race: Race,
+    config: Config,
+    config_path: PathBuf,
+    /// Setting that `--fullscreen` went over, until it's changed in the menu
+    fullscreen_over: Option<bool>,
+    /// Game is paused while a menu is open
+    menu: Option<Menu>,
+    stick: Stick,
+    /// Last command a stick sent each skier. A stick only sends another
+    /// once it changes, so one resting near the middle doesn't keep
+    /// centring the skier.
+    stick_commands: Vec<Command>,
+    audio: Audio,
+    achievements: Achievements,
+    /// Stats of every solo run, and where they're saved
+    totals: Totals,
+    totals_path: PathBuf,
+    /// Breakdown of the solo run once it's over and counted in the totals
+    run_summary: Option<String>,
+    ghosts: Ghosts,
+    /// Racing others on a server
+    client: Option<Client>,
+    /// Spectators watching
+    broadcast: Option<Broadcast>,
+    /// Attract mode, with a bot skiing for each skier
+    demo: bool,
+    bots: Vec<Bot>,
+    /// Run being played back instead of the player's input
+    replay: Option<Replay>,
+    /// Where the run is saved on quit, and the run so far
+    recording: Option<(PathBuf, Replay)>,
+    /// Where the telemetry is saved as each run ends, and the runs so far
+    telemetry: Option<(PathBuf, Telemetry)>,
+    /// Ticks since the run started
+    tick: u32,
+    /// Quit once this runs out
+    ticks_left: Option<u32>,
 }
 
 impl SkiFree {
-    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+    pub fn new(
+        ctx: &mut Context,
+        options: &Options,
+        replay: Option<Replay>,
+        client: Option<Client>,
+        broadcast: Option<Broadcast>,
+    ) -> GameResult<Self> {
         // Load/create resources such as images here.
         let assets = Assets::new(ctx)?;
-        let map = Map::new(&assets);
-        let player = Player::new(&assets);
-        Ok(Self {
+        let config_path = options.config.clone().unwrap_or_else(|| Config::path(ctx));
+        let config = Config::load(&config_path).unwrap_or_else(|e| {
+            eprintln!("error loading config, using defaults: {e:#}");
+            Config::default()
+        });
+        let players = config.settings.players as usize;
+        for skier in 0..players {
+            for (key, actions) in config.bindings(skier).conflicts() {
+                eprintln!(
+                    "warning: {key:?} is bound to several actions for player {}: {actions:?}",
+                    skier + 1
+                );
+            }
+        }
+        if players > 1 {
+            for key in config.shared_inputs() {
+                eprintln!("warning: {key:?} is bound for both players");
+            }
+        }
+        let achievements = Achievements::load(&Achievements::path(ctx)).unwrap_or_else(|e| {
+            eprintln!("error loading achievements: {e:#}");
+            Achievements::default()
+        });
+        let totals_path = Totals::path(ctx);
+        let totals = Totals::load(&totals_path).unwrap_or_else(|e| {
+            eprintln!("error loading stats: {e:#}");
+            Totals::default()
+        });
+        // the server or the replay picks the slope
+        let seed = replay
+            .as_ref()
+            .map(|r| r.seed)
+            .or(client.as_ref().map(Client::seed))
+            .or(options.seed)
+            .unwrap_or_else(|| OsRng.gen());
+        let course = replay.as_ref().map_or(options.course, |r| r.course);
+        let difficulty = replay
+            .as_ref()
+            .map(|r| r.difficulty)
+            .or(client.as_ref().map(Client::difficulty))
+            .or(options.difficulty)
+            .unwrap_or(config.settings.difficulty);
+        let map = Map::new(&assets, difficulty, seed);
+        // loaded by restart, once the difficulty is known
+        let ghosts = Ghosts::new(Ghosts::dir(ctx));
+        let audio = Audio::new(&config.settings);
+        let fullscreen_over = options.fullscreen.then_some(config.settings.fullscreen);
+        let mut game = Self {
             assets,
+            seed,
+            difficulty: options.difficulty,
+            course,
             map,
-            player,
-            hud: Default::default(),
-        })
+            skiers: vec![],
+            race: Race::new(0),
+            config,
+            config_path,
+            fullscreen_over,
+            menu: None,
+            stick: Default::default(),
+            stick_commands: vec![],
+            audio,
+            achievements,
+            totals,
+            totals_path,
+            run_summary: None,
+            ghosts,
+            client,
+            broadcast,
+            demo: options.demo,
+            bots: vec![],
+            recording: options
+                .record
+                .clone()
+                .map(|path| (path, Replay::new(seed, difficulty, course))),
+            telemetry: options
+                .telemetry
+                .clone()
+                .map(|path| (path, Telemetry::default())),
+            replay,
+            tick: 0,
+            ticks_left: options.ticks,
+        };
+        game.restart();
+        game.apply_settings(ctx)?;
+        if options.window.is_none() {
+            game.fit_window(ctx, 1.)?;
+        }
+        Ok(game)
     }
 
-    fn handle_collisions(&mut self) {
-        if let Some(action) = self.map.check_collision(&self.player) {
-            self.player.collision(action);
+    /// Start over on the same slope
+    fn restart(&mut self) {
+        let settings = &self.config.settings;
+        self.map = Map::new(&self.assets, self.difficulty(), self.seed);
+        self.skiers = (0..self.players())
+            .map(|_| Skier::new(&self.assets, settings.show_minimap))
+            .collect();
+        if let Some(course) = self.course {
+            for skier in &mut self.skiers {
+                skier.player.place(course.start());
+            }
+        }
+        self.race = Race::new(self.skiers.len());
+        self.stick_commands = vec![Command::Steer(None); self.skiers.len()];
+        self.bots = if self.demo {
+            vec![Bot::default(); self.skiers.len()]
+        } else {
+            vec![]
+        };
+        self.achievements.reset_run();
+        self.run_summary = None;
+        let difficulty = self.difficulty();
+        self.ghosts.restart(Slope {
+            seed: self.seed,
+            difficulty,
+            start: self.course,
+        });
+        self.tick = 0;
+        if let Some((_, run)) = &mut self.recording {
+            *run = Replay::new(self.seed, difficulty, self.course);
+        }
+        self.save_telemetry();
+        if let Some((_, telemetry)) = &mut self.telemetry {
+            telemetry.next_run();
+        }
+        if let Some(broadcast) = &mut self.broadcast {
+            broadcast.start(self.seed, difficulty);
+        }
+    }
+
+    /// Every skier on the slope, as sent to spectators
+    fn broadcast_skiers(&self) -> Vec<SpectatedSkier> {
+        let local = self.skiers.iter().map(|s| {
+            let run = RunStatus {
+                time: s.hud.elapsed_time,
+                distance: s.hud.distance,
+                speed: s.hud.speed,
+                score: s.score,
+            };
+            let player = &s.player;
+            (
+                player.position(),
+                player.pose(),
+                Some(run),
+                s.yeti.sighting(),
+            )
+        });
+        let remote = self.client.iter().flat_map(|client| {
+            client
+                .skiers()
+                .into_iter()
+                .map(|s| (Vec2::new(s.x, s.y), s.pose, None, None))
+        });
+        local
+            .chain(remote)
+            .enumerate()
+            .map(|(id, (position, pose, run, yeti))| SpectatedSkier {
+                skier: RemoteSkier {
+                    id: id as u8,
+                    x: position.x,
+                    y: position.y,
+                    pose,
+                },
+                run,
+                yeti,
+            })
+            .collect()
+    }
+
+    fn new_slope(&mut self) {
+        if self.client.is_some() || self.replay.is_some() {
+            return self.restart();
+        }
+        self.seed = OsRng.gen();
+        self.restart();
+    }
+
+    fn is_race(&self) -> bool {
+        self.skiers.len() > 1 || self.client.is_some()
+    }
+
+    /// Someone skiing alone, who earns achievements and ghosts
+    fn is_solo(&self) -> bool {
+        !self.is_race() && !self.demo && self.replay.is_none()
+    }
+
+    /// Add the solo run to the totals and save them, once a run. Runs given
+    /// up on count too, as long as they got going.
+    fn count_run(&mut self) {
+        if !self.is_solo() || self.run_summary.is_some() || self.tick == 0 {
+            return;
+        }
+        let run = &self.skiers[0].stats;
+        self.totals.add(run);
+        if let Err(e) = self.totals.save(&self.totals_path) {
+            eprintln!("error saving stats: {e:#}");
         }
+        self.run_summary = Some(self.totals.summary(run));
+    }
+
+    /// Stop the demo and hand the slope over to the player
+    fn end_demo(&mut self) {
+        self.demo = false;
+        self.restart();
+    }
+
+    /// Skiers on this screen. Only one when racing online or watching a
+    /// replay.
+    fn players(&self) -> usize {
+        if self.client.is_some() || self.replay.is_some() {
+            1
+        } else {
+            self.config.settings.players as usize
+        }
+    }
+
+    fn difficulty(&self) -> Difficulty {
+        self.client
+            .as_ref()
+            .map(Client::difficulty)
+            .or(self.replay.as_ref().map(|r| r.difficulty))
+            .or(self.difficulty)
+            .unwrap_or(self.config.settings.difficulty)
+    }
+
+    fn apply_settings(&mut self, ctx: &mut Context) -> GameResult {
+        if self.skiers.len() != self.players() {
+            // keep the scale the window had
+            let scale = self.scale(ctx);
+            self.restart();
+            self.fit_window(ctx, scale)?;
+        }
+        let settings = &self.config.settings;
+        for skier in &mut self.skiers {
+            skier.hud = skier.hud.set_show_minimap(settings.show_minimap);
+        }
+        self.audio.apply_settings(settings);
+        if self
+            .fullscreen_over
+            .is_some_and(|f| f != settings.fullscreen)
+        {
+            self.fullscreen_over = None;
+        }
+        ctx.gfx
+            .set_fullscreen(if settings.fullscreen || self.fullscreen_over.is_some() {
+                FullscreenType::Desktop
+            } else {
+                FullscreenType::Windowed
+            })
+    }
+
+    /// Skin skier number `index` is drawn with. The first skier gets the
+    /// chosen one as long as it's still unlocked, the second is always blue
+    /// so they can be told apart.
+    fn skin(&self, index: usize) -> Skin {
+        let skin = self.config.settings.skin;
+        if index > 0 {
+            Skin::Ice
+        } else if self.achievements.skins().contains(&skin) {
+            skin
+        } else {
+            Skin::Classic
+        }
+    }
+
+    /// Save every run logged so far, so that a run isn't lost once the next
+    /// one starts
+    fn save_telemetry(&self) {
+        let Some((path, telemetry)) = &self.telemetry else {
+            return;
+        };
+        if telemetry.samples().is_empty() {
+            return;
+        }
+        match telemetry.save(path) {
+            Ok(()) => eprintln!("telemetry saved to {}", path.display()),
+            Err(e) => eprintln!("error saving telemetry: {e:#}"),
+        }
+    }
+
+    fn save_config(&self) {
+        if let Err(e) = self.config.save(&self.config_path) {
+            eprintln!("error saving config: {e:#}");
+        }
+    }
+
+    fn command(&mut self, ctx: &mut Context, skier: usize, command: Command) {
+        match command {
+            Command::Pressed(action) => self.action_pressed(ctx, skier, action),
+            Command::Released(action) => self.action_released(skier, action),
+            Command::Steer(angle) => self.play(
+                skier,
+                angle.map_or(PlayerInput::Center, |angle| PlayerInput::Steer { angle }),
+            ),
+        }
+    }
+
+    /// Send a command for every action bound to the input, to each skier
+    /// that has it bound
+    fn input(&mut self, ctx: &mut Context, input: Input, pressed: bool) {
+        for skier in 0..self.skiers.len() {
+            let actions: Vec<_> = self.config.bindings(skier).actions(input).collect();
+            for action in actions {
+                self.command(
+                    ctx,
+                    skier,
+                    if pressed {
+                        Command::Pressed(action)
+                    } else {
+                        Command::Released(action)
+                    },
+                );
+            }
+        }
+    }
+
+    fn action_pressed(&mut self, ctx: &mut Context, skier: usize, action: Action) {
+        match action {
+            Action::SteerLeft => self.play(skier, PlayerInput::Left),
+            Action::SteerRight => self.play(skier, PlayerInput::Right),
+            Action::Trick1 => self.trick(skier, TrickType::Trick1),
+            Action::Trick2 => self.trick(skier, TrickType::Trick2),
+            Action::Flip => self.trick(skier, TrickType::Flip),
+            Action::Boost => self.play(skier, PlayerInput::Boost { boosting: true }),
+            Action::Pause => self.set_menu(Some(Menu::pause(self.achievements.skins()))),
+            Action::Quit => ctx.request_quit(),
+            // comes as `Command::Steer` from the stick instead
+            Action::Steer => {}
+            Action::ToggleMinimap => {
+                let settings = &mut self.config.settings;
+                settings.show_minimap = !settings.show_minimap;
+                for skier in &mut self.skiers {
+                    skier.hud = skier.hud.set_show_minimap(settings.show_minimap);
+                }
+                self.save_config();
+            }
+        }
+    }
+
+    fn action_released(&mut self, skier: usize, action: Action) {
+        match action {
+            Action::SteerLeft => self.play(skier, PlayerInput::SlideLeft),
+            Action::SteerRight => self.play(skier, PlayerInput::SlideRight),
+            Action::Boost => self.play(skier, PlayerInput::Boost { boosting: false }),
+            _ => {}
+        }
+    }
+
+    fn trick(&mut self, skier: usize, trick: TrickType) {
+        self.play(skier, PlayerInput::Trick { trick });
+    }
+
+    /// Apply a player's input to their skier, recording it. A replayed
+    /// skier doesn't listen to the player.
+    fn play(&mut self, skier: usize, input: PlayerInput) {
+        if self.replay.is_some() {
+            return;
+        }
+        if let Some((_, run)) = &mut self.recording {
+            if skier == 0 {
+                run.record(self.tick, input);
+            }
+        }
+        input.apply(&mut self.skiers[skier].player);
+    }
+
+    fn handle_menu_event(&mut self, ctx: &mut Context, event: MenuEvent) -> GameResult {
+        match event {
+            MenuEvent::Resume => self.set_menu(None),
+            // online, the server restarts everyone together
+            MenuEvent::Restart | MenuEvent::NewSlope if self.client.is_some() => {
+                if let Some(Err(e)) = self.client.as_ref().map(Client::restart) {
+                    eprintln!("error asking for a restart: {e:#}");
+                }
+                self.set_menu(None);
+            }
+            MenuEvent::Restart => {
+                self.count_run();
+                self.restart();
+                self.set_menu(None);
+            }
+            MenuEvent::NewSlope => {
+                self.count_run();
+                self.new_slope();
+                self.set_menu(None);
+            }
+            MenuEvent::Quit => ctx.request_quit(),
+            MenuEvent::SettingsChanged => {
+                self.apply_settings(ctx)?;
+                self.save_config();
+            }
+            MenuEvent::BindingsChanged => self.save_config(),
+        }
+        Ok(())
+    }
+
+    /// Let the server know we're still here while paused
+    fn keep_alive(&mut self) {
+        let Some(client) = &mut self.client else {
+            return;
+        };
+        let player = &self.skiers[0].player;
+        if let Err(e) = client.keep_alive(player.position(), player.pose()) {
+            eprintln!("lost the server: {e:#}");
+            self.client = None;
+        }
+    }
+
+    fn set_menu(&mut self, menu: Option<Menu>) {
+        self.audio.pause(menu.is_some());
+        self.menu = menu;
+    }
+
+    /// Hand out the events of the last tick to everything listening
+    fn dispatch_events(&mut self, ctx: &Context) {
+        let solo = self.is_solo();
+        for (index, skier) in self.skiers.iter_mut().enumerate() {
+            let events: Vec<_> = skier.player.events().drain().collect();
+            let mut subscribers: Vec<&mut dyn Subscriber> = vec![
+                &mut skier.score,
+                &mut skier.hud,
+                &mut skier.stats,
+                &mut self.audio,
+            ];
+            if let Some(bot) = self.bots.get_mut(index) {
+                subscribers.push(bot);
+            }
+            // achievements and ghosts are for playing alone
+            if solo {
+                subscribers.push(&mut self.achievements);
+                subscribers.push(&mut self.ghosts);
+            }
+            for subscriber in subscribers {
+                for event in &events {
+                    subscriber.on_event(event);
+                }
+            }
+            if let Some((_, telemetry)) = &mut self.telemetry {
+                telemetry.record(self.tick, index, &skier.player, &self.map, &events);
+            }
+            if events
+                .iter()
+                .any(|e| matches!(e, GameEvent::CourseFinished { .. }))
+            {
+                self.race.finish(index, skier.hud.elapsed_time);
+                if let Some(client) = &mut self.client {
+                    client.finish();
+                }
+            }
+        }
+
+        self.ghosts.save();
+
+        // announced with the next tick's events
+        let unlocked = self.achievements.take_new();
+        if !unlocked.is_empty() {
+            if let Err(e) = self.achievements.save(&Achievements::path(ctx)) {
+                eprintln!("error saving achievements: {e:#}");
+            }
+        }
+        for achievement in unlocked {
+            self.skiers[0]
+                .player
+                .events()
+                .emit(GameEvent::AchievementUnlocked(achievement));
+        }
+    }
+
+    /// Least of the slope that has to be seen, a view from the settings
+    /// for each skier side by side
+    fn view_size(&self) -> Vec2 {
+        let settings = &self.config.settings;
+        Vec2::new(
+            settings.view_width as f32 * self.skiers.len() as f32,
+            settings.view_height as f32,
+        )
+    }
+
+    /// Pixels per pixel of the sprites
+    fn scale(&self, ctx: &Context) -> f32 {
+        integer_scale(ctx.gfx.drawable_size().into(), self.view_size())
+    }
+
+    /// Resize the window to fit the view at `scale`
+    fn fit_window(&self, ctx: &mut Context, scale: f32) -> GameResult {
+        let size = self.view_size() * scale;
+        ctx.gfx.set_drawable_size(size.x, size.y)
+    }
+
+    /// The window in unscaled coordinates
+    fn screen(&self, ctx: &Context) -> Rect {
+        let (width, height) = ctx.gfx.drawable_size();
+        let scale = self.scale(ctx);
+        Rect::new(0., 0., width / scale, height / scale)
+    }
+
+    /// Area of the screen each skier is shown in, side by side
+    fn viewports(&self, ctx: &Context) -> Vec<Rect> {
+        let screen = self.screen(ctx);
+        let width = screen.w / self.skiers.len() as f32;
+        (0..self.skiers.len())
+            .map(|i| Rect::new(i as f32 * width, 0., width, screen.h))
+            .collect()
     }
 }
 
 impl EventHandler for SkiFree {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
-        const DESIRED_FPS: u32 = 60;
+        while ctx.time.check_update_time(TICKS_PER_SECOND) {
+            // keep draining the timer while paused so the simulation doesn't
+            // try to catch up on resume
+            if self.menu.is_some() {
+                self.keep_alive();
+                continue;
+            }
+
+            let seconds = 1.0 / (TICKS_PER_SECOND as f32);
+
+            if let Some(replay) = &self.replay {
+                for input in replay.inputs_at(self.tick) {
+                    input.apply(&mut self.skiers[0].player);
+                }
+            }
+            for (skier, bot) in self.skiers.iter_mut().zip(&self.bots) {
+                bot.update(&mut skier.player, &self.map);
+            }
+            for (index, skier) in self.skiers.iter_mut().enumerate() {
+                skier.update(index, &mut self.map, &self.assets);
+            }
+            if self.demo && self.skiers.iter().all(Skier::is_over) {
+                self.new_slope();
+            }
+            self.map.update();
+            self.tick += 1;
 
-        while ctx.time.check_update_time(DESIRED_FPS) {
-            let seconds = 1.0 / (DESIRED_FPS as f32);
+            if self.is_solo() {
+                let player = &self.skiers[0].player;
+                self.ghosts.record(player.position(), player.pose());
+            }
+            if let Some(client) = &mut self.client {
+                let player = &self.skiers[0].player;
+                if let Err(e) = client.update(player.position(), player.pose()) {
+                    eprintln!("lost the server: {e:#}");
+                    self.client = None;
+                }
+            }
+            if self.client.as_mut().is_some_and(Client::take_restart) {
+                self.restart();
+            }
+            if self.broadcast.is_some() {
+                let skiers = self.broadcast_skiers();
+                if let Some(broadcast) = &mut self.broadcast {
+                    broadcast.update(skiers);
+                }
+            }
 
-            self.handle_collisions();
+            let race = self.is_race();
+            for skier in &mut self.skiers {
+                let position = skier.player.position();
+                let course = skier.progress.course();
+                // a racer's clock stops at the finish line
+                let seconds = if race && skier.progress.is_finished() {
+                    0.
+                } else {
+                    seconds
+                };
+                skier.hud = skier
+                    .hud
+                    .set_distance(skier.player.distance())
+                    .set_speed(skier.player.current_speed() * TICKS_PER_SECOND as f32)
+                    .set_style(skier.score.style)
+                    .set_position(position, course)
+                    .add_time(seconds);
+            }
+            if !race {
+                let skier = &mut self.skiers[0];
+                let delta = self
+                    .ghosts
+                    .delta(skier.progress.course(), skier.player.position());
+                skier.hud = skier
+                    .hud
+                    .set_ghost_delta(delta.map(|ticks| ticks as f32 / TICKS_PER_SECOND as f32));
+            }
 
-            self.map.update(&self.assets, &self.player);
-            self.hud = self
-                .hud
-                .set_distance(self.map.y_distance())
-                .add_time(seconds);
+            self.dispatch_events(ctx);
+            if self.skiers[0].is_over() {
+                self.count_run();
+            }
 
-            self.player.maybe_next_state(&self.assets);
+            if let Some(ticks_left) = &mut self.ticks_left {
+                *ticks_left = ticks_left.saturating_sub(1);
+                if *ticks_left == 0 {
+                    ctx.request_quit();
+                    break;
+                }
+            }
         }
         Ok(())
     }
 
+    fn quit_event(&mut self, _ctx: &mut Context) -> GameResult<bool> {
+        self.count_run();
+        if let Some((path, run)) = &self.recording {
+            match run.save(path) {
+                Ok(()) => eprintln!("run recorded to {}", path.display()),
+                Err(e) => eprintln!("error saving the run: {e:#}"),
+            }
+        }
+        self.save_telemetry();
+        Ok(false)
+    }
+
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         let mut canvas = Canvas::from_frame(ctx, Color::WHITE);
         canvas.set_sampler(Sampler::nearest_clamp());
+        // sharp pixels, scaled up by a whole number
+        let scale = self.scale(ctx);
+        canvas.set_screen_coordinates(self.screen(ctx));
+
+        for (index, viewport) in self.viewports(ctx).into_iter().enumerate() {
+            let skier = &self.skiers[index];
+            let camera = skier.camera(viewport);
+            canvas.set_scissor_rect(Rect::new(
+                viewport.x * scale,
+                viewport.y * scale,
+                viewport.w * scale,
+                viewport.h * scale,
+            ))?;
+
+            self.map
+                .draw(ctx, &mut canvas, skier.view(viewport), camera, index);
+            if !self.is_race() {
+                self.ghosts
+                    .draw(&mut canvas, &self.assets, skier.progress.course(), camera);
+            }
+            if let Some(client) = &self.client {
+                let near = skier.player.position().x;
+                for remote in client.skiers() {
+                    canvas.draw(
+                        remote.pose.image(&self.assets).as_ref(),
+                        DrawParam::default()
+                            .dest(Vec2::new(wrap_x(remote.x, near), remote.y) + camera)
+                            .color(Skin::Ice.tint()),
+                    );
+                }
+            }
+            for (i, other) in self.skiers.iter().enumerate() {
+                other
+                    .player
+                    .draw(ctx, &self.assets, &mut canvas, self.skin(i), camera);
+                other.yeti.draw(ctx, &self.assets, &mut canvas, camera);
+            }
+            skier.hud.draw(ctx, &mut canvas, viewport);
+            if index > 0 {
+                let divider = [
+                    Vec2::new(viewport.x, viewport.y),
+                    Vec2::new(viewport.x, viewport.bottom()),
+                ];
+                canvas.draw(
+                    &Mesh::new_line(ctx, &divider, 2., Color::BLACK)?,
+                    DrawParam::default(),
+                );
+            }
+        }
+        canvas.set_default_scissor_rect();
 
-        self.map.draw(ctx, &mut canvas);
-        self.player.draw(ctx, &self.assets, &mut canvas);
-        self.hud.draw(ctx, &mut canvas);
+        if let Some(client) = &self.client {
+            if !client.results().is_empty() {
+                let lines: Vec<_> = client
+                    .results()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| {
+                        let time = format_time(s.ticks as f32 / TICKS_PER_SECOND as f32);
+                        format!("{}. {} {time}", i + 1, s.name)
+                    })
+                    .collect();
+                draw_banner(ctx, &mut canvas, &lines.join("\n"));
+            }
+        } else if let Some(result) = self.race.result() {
+            draw_banner(ctx, &mut canvas, &result);
+        } else if let Some(summary) = &self.run_summary {
+            draw_banner(ctx, &mut canvas, summary);
+        }
+        if let Some(menu) = &self.menu {
+            menu.draw(ctx, &mut canvas, &self.config);
+        }
 
         canvas.finish(ctx)?;
 
@@ -89,36 +798,276 @@ impl EventHandler for SkiFree {
 
     fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeated: bool) -> GameResult {
         if let Some(keycode) = input.keycode {
+            if let Some(menu) = &mut self.menu {
+                if let Some(event) = menu.key_down(keycode, &mut self.config) {
+                    self.handle_menu_event(ctx, event)?;
+                }
+                return Ok(());
+            }
             if repeated {
                 return Ok(());
             }
-            match keycode {
-                VirtualKeyCode::Escape | VirtualKeyCode::Q => ctx.request_quit(),
-                VirtualKeyCode::Left => self.player.left(),
-                VirtualKeyCode::Right => self.player.right(),
-                VirtualKeyCode::Z => self.player.do_trick(TrickType::Trick1),
-                VirtualKeyCode::X => self.player.do_trick(TrickType::Trick2),
-                VirtualKeyCode::C => self.player.do_trick(TrickType::Flip),
-                _ => {}
+            if self.demo {
+                self.end_demo();
+                return Ok(());
             }
+            self.input(ctx, Input::Key(keycode), true);
         }
         Ok(())
     }
 
-    /// A keyboard button was released.
-    fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
+    /// A keyboard button was released. Releases get through the menu too,
+    /// so a skier doesn't keep turning after resuming.
+    fn key_up_event(&mut self, ctx: &mut Context, input: KeyInput) -> GameResult {
         if let Some(keycode) = input.keycode {
-            match keycode {
-                VirtualKeyCode::Left => self.player.slide_left(),
-                VirtualKeyCode::Right => self.player.slide_right(),
-                _ => {}
+            self.input(ctx, Input::Key(keycode), false);
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: Button,
+        _id: GamepadId,
+    ) -> GameResult {
+        if let Some(menu) = &mut self.menu {
+            if let Some(event) = menu.button_down(button, &mut self.config) {
+                self.handle_menu_event(ctx, event)?;
+            }
+            return Ok(());
+        }
+        if self.demo {
+            self.end_demo();
+            return Ok(());
+        }
+        self.input(ctx, Input::Button(button), true);
+        Ok(())
+    }
+
+    fn gamepad_button_up_event(
+        &mut self,
+        ctx: &mut Context,
+        button: Button,
+        _id: GamepadId,
+    ) -> GameResult {
+        self.input(ctx, Input::Button(button), false);
+        Ok(())
+    }
+
+    fn gamepad_axis_event(
+        &mut self,
+        ctx: &mut Context,
+        axis: Axis,
+        value: f32,
+        _id: GamepadId,
+    ) -> GameResult {
+        let Some((stick, command)) = self.stick.axis(axis, value) else {
+            return Ok(());
+        };
+        if let Some(menu) = &mut self.menu {
+            if command != Command::Steer(None) {
+                if let Some(event) = menu.stick_pushed(stick, &mut self.config) {
+                    self.handle_menu_event(ctx, event)?;
+                }
+                return Ok(());
             }
         }
+        // letting go gets through the menu, like releasing a key
+        for skier in 0..self.skiers.len() {
+            let bindings = self.config.bindings(skier);
+            if bindings.actions(Input::Stick(stick)).any(|a| a.is_analog())
+                && self.stick_commands[skier] != command
+            {
+                self.stick_commands[skier] = command;
+                self.command(ctx, skier, command);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Text in a box in the middle of the window
+fn draw_banner(ctx: &Context, canvas: &mut Canvas, text: &str) {
+    let size = screen_size(ctx, canvas);
+    let (width, height) = (size.x, size.y);
+    let mut text = Text::new(text);
+    text.set_scale(PxScale::from(24.));
+    let size = text.measure(ctx).map_or(Vec2::ZERO, Vec2::from);
+    let padding = 16.;
+    let panel = Rect::new(
+        (width - size.x) / 2. - padding,
+        (height - size.y) / 3. - padding,
+        size.x + padding * 2.,
+        size.y + padding * 2.,
+    );
+    if let Ok(fill) = Mesh::new_rectangle(ctx, DrawMode::fill(), panel, Color::WHITE) {
+        canvas.draw(&fill, DrawParam::default());
+    }
+    if let Ok(border) = Mesh::new_rectangle(ctx, DrawMode::stroke(2.), panel, Color::BLACK) {
+        canvas.draw(&border, DrawParam::default());
+    }
+    canvas.draw(
+        &text,
+        DrawParam::new()
+            .dest([panel.x + padding, panel.y + padding])
+            .color(Color::BLACK),
+    );
+}
+
+/// Ski a run without a window, playing back the replay or letting the bot
+/// ski, and print how it went
+fn run_headless(options: &Options, replay: Option<Replay>) -> Result<()> {
+    // ten minutes, for bots that get stuck
+    const MAX_TICKS: u32 = 36_000;
+
+    let seed = replay
+        .as_ref()
+        .map(|r| r.seed)
+        .or(options.seed)
+        .unwrap_or_else(|| OsRng.gen());
+    let difficulty = replay
+        .as_ref()
+        .map(|r| r.difficulty)
+        .or(options.difficulty)
+        .unwrap_or(Difficulty::Normal);
+    let course = replay.as_ref().map_or(options.course, |r| r.course);
+
+    let mut sim = Simulation::new(seed, difficulty);
+    if let Some(course) = course {
+        sim.skier.player.place(course.start());
+    }
+    let mut bot = Bot::default();
+    let mut telemetry = Telemetry::default();
+
+    let renderer = (!options.snapshot.is_empty())
+        .then(Renderer::new)
+        .transpose()?;
+    let (width, height) = options.window.map_or_else(
+        || {
+            let settings = Settings::default();
+            (settings.view_width as u32, settings.view_height as u32)
+        },
+        |(width, height)| (width as u32, height as u32),
+    );
+    let snap = |sim: &Simulation| -> Result<()> {
+        let Some(renderer) = &renderer else {
+            return Ok(());
+        };
+        if options.snapshot.contains(&sim.tick()) {
+            let frame = renderer.render(sim.assets(), &sim.map, &sim.skier, width, height);
+            let path = PathBuf::from(format!("snapshot-{seed}-{}.png", sim.tick()));
+            snapshot::save(&frame, &path)?;
+            println!("saved {}", path.display());
+        }
         Ok(())
+    };
+
+    let last_tick = options
+        .ticks
+        .or(options.snapshot.last().copied())
+        .unwrap_or(MAX_TICKS);
+    snap(&sim)?;
+    while !sim.is_over() && sim.tick() < last_tick {
+        match &replay {
+            Some(replay) => {
+                for input in replay.inputs_at(sim.tick()) {
+                    input.apply(&mut sim.skier.player);
+                }
+            }
+            None => bot.update(&mut sim.skier.player, &sim.map),
+        }
+        let events = sim.step();
+        for event in &events {
+            bot.on_event(event);
+        }
+        if options.telemetry.is_some() {
+            telemetry.record(sim.tick(), 0, &sim.skier.player, &sim.map, &events);
+        }
+        snap(&sim)?;
+    }
+
+    let skier = &sim.skier;
+    let score = &skier.score;
+    let outcome = if skier.progress.is_finished() {
+        "finished"
+    } else if skier.player.is_eaten() {
+        "eaten by the yeti"
+    } else {
+        "still skiing"
+    };
+    println!("slope {seed} ({})", difficulty.name());
+    println!(
+        "{outcome} after {}",
+        format_time(sim.tick() as f32 / TICKS_PER_SECOND as f32)
+    );
+    println!("distance {:.0}", skier.player.distance());
+    println!("style {}", score.style);
+    println!(
+        "gates {} passed, {} missed",
+        score.gates_passed, score.gates_missed
+    );
+    for line in skier.stats.lines() {
+        println!("{line}");
+    }
+    if let Some(path) = &options.telemetry {
+        telemetry.save(path)?;
+        println!("telemetry saved to {}", path.display());
+    }
+    Ok(())
+}
+
+fn export(options: &Options, replay: &Replay, path: &Path) -> Result<()> {
+    let mut export = Export {
+        scale: options.scale.unwrap_or(1),
+        show_hud: !options.no_hud,
+        ..Default::default()
+    };
+    if let Some(frames) = &options.frames {
+        export.ticks = frames.clone();
     }
+    if let Some((width, height)) = options.window {
+        (export.width, export.height) = (width as u32, height as u32);
+    }
+    let frames = export.run(replay, path)?;
+    println!("saved {frames} frames to {}", path.display());
+    Ok(())
+}
+
+/// Explain what's wrong with the arguments, without a backtrace
+fn exit_with_error(message: String) -> ! {
+    eprintln!("skifree-rs: {message}");
+    std::process::exit(2);
 }
 
-fn main() -> Result<!> {
+fn main() -> Result<()> {
+    let options = Options::parse(std::env::args().skip(1))
+        .unwrap_or_else(|e| exit_with_error(format!("{e:#}\n\n{USAGE}")));
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    if options.version {
+        println!(
+            "skifree-rs {} (assets {})",
+            env!("CARGO_PKG_VERSION"),
+            assets::MANIFEST_HASH
+        );
+        return Ok(());
+    }
+    let replay = options
+        .replay
+        .as_deref()
+        .map(Replay::load)
+        .transpose()
+        .unwrap_or_else(|e| exit_with_error(format!("{e:#}")));
+    if let (Some(path), Some(replay)) = (&options.export, &replay) {
+        return export(&options, replay, path);
+    }
+    if options.headless || !options.snapshot.is_empty() {
+        return run_headless(&options, replay);
+    }
+
     // We add the CARGO_MANIFEST_DIR/resources to the resource paths
     // so that ggez will look in our cargo project directory for files.
     let resource_dir = if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
@@ -127,15 +1076,45 @@ fn main() -> Result<!> {
         PathBuf::from("./assets")
     };
 
+    let spectate = options
+        .spectate
+        .as_ref()
+        .map(|addr| Watch::connect(addr).with_context(|| format!("watching {addr}")))
+        .transpose()?;
+    let client = match &options.connect {
+        Some(addr) => {
+            let name = std::env::var("USER").unwrap_or_else(|_| "skier".into());
+            let client = Client::connect(addr, &name, Duration::from_secs(5))
+                .with_context(|| format!("joining {addr}"))?;
+            Some(client)
+        }
+        None => None,
+    };
+    let broadcast = options
+        .broadcast
+        .as_ref()
+        .map(|addr| Broadcast::listen(addr).with_context(|| format!("broadcasting on {addr}")))
+        .transpose()?;
+
+    let (width, height) = options.window.unwrap_or((WINDOW_WIDTH, WINDOW_HEIGHT));
     let (mut ctx, event_loop) = ContextBuilder::new("skifree-rs", "trevarj")
         .add_resource_path(resource_dir)
         .window_setup(WindowSetup::default().title("SkiFree"))
         .window_mode(
             WindowMode::default()
-                .resizable(false)
-                .dimensions(WINDOW_WIDTH, WINDOW_HEIGHT),
+                .resizable(true)
+                .dimensions(width, height),
         )
-        .build()?;
-    let game = SkiFree::new(&mut ctx)?;
+        .build()
+        .context("opening the window")?;
+    if let Some(watch) = spectate {
+        // the same view of the slope as playing
+        let config_path = options.config.clone().unwrap_or_else(|| Config::path(&ctx));
+        let settings =
+            Config::load(&config_path).map_or_else(|_| Settings::default(), |c| c.settings);
+        let spectator = Spectator::new(&mut ctx, watch, &settings)?;
+        event::run(ctx, event_loop, spectator);
+    }
+    let game = SkiFree::new(&mut ctx, &options, replay, client, broadcast)?;
     event::run(ctx, event_loop, game);
 }
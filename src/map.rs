@@ -1,18 +1,26 @@
-use ggez::graphics::{Canvas, Color, DrawParam, Mesh};
+use std::ops::Range;
+use std::rc::Rc;
+
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, DrawMode, DrawParam, Image, InstanceArray, Mesh, MeshBuilder, Rect};
 use ggez::Context;
-use rand::rngs::OsRng;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-use self::objects::{LineObject, Shift};
-use crate::assets::Assets;
+use self::objects::{Gate, GateSide, LineObject, ObjectKind};
+use self::terrain::{Surface, Terrain};
+use crate::animation::{DOG_WAG, NOOB_FALL};
+use crate::assets::{Assets, Sprite};
+use crate::events::GameEvent;
 use crate::map::objects::Object;
 use crate::player::{CollisionAction, Player};
-use crate::util::{draw_hitbox, vec2_from_angle};
-use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::settings::Difficulty;
+use crate::util::draw_hitbox;
 
-const MAP_WIDTH: i32 = 3000;
-const MAP_HEIGHT: i32 = 20_000;
-const MAP_X_START: i32 = SLALOM_X_START;
+pub const MAP_WIDTH: i32 = 3000;
+pub const MAP_HEIGHT: i32 = 20_000;
+pub const MAP_X_START: i32 = SLALOM_X_START;
 
 const COURSE_WIDTH: i32 = MAP_WIDTH / 3;
 const COURSE_Y_START: i32 = 300;
@@ -20,28 +28,120 @@ const SLALOM_X_START: i32 = MAP_WIDTH / -2;
 const FREESTYLE_X_START: i32 = SLALOM_X_START + COURSE_WIDTH;
 const TREE_SLALOM_X_START: i32 = FREESTYLE_X_START + COURSE_WIDTH;
 
-const LIFT_X_POS: f32 = 100.;
+pub const LIFT_X_POS: f32 = 100.;
 const LIFT_Y_START: i32 = 100;
+/// Where the chairlift drops the skier off
+pub const LIFT_TOP: f32 = LIFT_Y_START as f32;
+
+const GATE_SPACING: usize = 300;
+/// Every course ends here
+pub const FINISH_Y: i32 = 10_000;
 
-mod objects;
+/// Distance between `DistanceMilestone` events
+pub const MILESTONE_DISTANCE: u32 = 1000;
+
+pub mod objects;
 mod slalom;
+pub mod terrain;
 
 pub struct Map {
     objects: Vec<Object>,
     lines: Vec<LineObject>,
-    rng: OsRng,
-    y_distance: f32,
+    /// What the snow is like, under the objects
+    terrain: Terrain,
+    rng: StdRng,
+    gate_count: u32,
+    /// Gate images once a skier went by
+    gate_passed: Rc<Sprite>,
+    gate_missed: Rc<Sprite>,
+    atlas: Option<Image>,
+    /// Objects in view, drawn in one go. Every skier has their own, as a
+    /// batch can only be drawn once a frame.
+    batches: Vec<InstanceArray>,
+}
+
+/// How far a skier has got down the slope
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Progress {
+    course: Option<Course>,
+    milestone: u32,
+    gates_passed: u32,
+    finished: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Course {
+    Slalom,
+    Freestyle,
+    TreeSlalom,
+}
+
+impl Course {
+    pub const ALL: [Course; 3] = [Course::Slalom, Course::Freestyle, Course::TreeSlalom];
+
+    /// Course the given map position is on, if any
+    pub fn at(position: Vec2) -> Option<Course> {
+        if position.y < COURSE_Y_START as f32 {
+            return None;
+        }
+        Self::ALL
+            .into_iter()
+            .find(|c| c.x_range().contains(&(position.x as i32)))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Course::Slalom => "slalom",
+            Course::Freestyle => "freestyle",
+            Course::TreeSlalom => "tree slalom",
+        }
+    }
+
+    /// Name for use in file names
+    pub fn id(&self) -> &'static str {
+        match self {
+            Course::Slalom => "slalom",
+            Course::Freestyle => "freestyle",
+            Course::TreeSlalom => "tree_slalom",
+        }
+    }
+
+    /// Where a skier starting on the course starts, at the top in the middle
+    pub fn start(&self) -> Vec2 {
+        let range = self.x_range();
+        Vec2::new((range.start + range.end) as f32 / 2., Player::POSITION[1])
+    }
+
+    pub fn x_range(&self) -> Range<i32> {
+        match self {
+            Course::Slalom => SLALOM_X_START..FREESTYLE_X_START,
+            Course::Freestyle => FREESTYLE_X_START..TREE_SLALOM_X_START,
+            Course::TreeSlalom => TREE_SLALOM_X_START..MAP_WIDTH,
+        }
+    }
 }
 
 impl Map {
-    pub fn new(assets: &Assets) -> Self {
-        let mut rng = OsRng;
+    /// Generate the slope. The same seed always gives the same slope.
+    pub fn new(assets: &Assets, difficulty: Difficulty, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let density = difficulty.obstacle_density();
         let mut objects = vec![];
         objects.extend(starting_objects(assets));
-        objects.extend(slalom_course(assets, &mut rng));
-        objects.extend(freestyle_course(assets, &mut rng));
-        objects.extend(tree_slalom_course(assets, &mut rng));
+        objects.extend(slalom_course(assets, &mut rng, density));
+        let gates = slalom_gates(assets);
+        let gate_count = gates
+            .iter()
+            .filter(|o| matches!(o.kind, ObjectKind::Gate(_)))
+            .count() as u32;
+        objects.extend(gates);
+        objects.extend(freestyle_course(assets, &mut rng, density));
+        objects.extend(tree_slalom_course(assets, &mut rng, density));
+        objects.extend(Course::ALL.into_iter().flat_map(|c| finish_line(assets, c)));
         objects.extend(ski_lift(assets));
+        // after the objects, so they're where they always were
+        let terrain = Terrain::generate(&mut rng, density);
 
         let lines = vec![
             // Ski lift lines
@@ -65,67 +165,249 @@ impl Map {
         Self {
             objects,
             lines,
+            terrain,
             rng,
-            y_distance: 0.,
+            gate_count,
+            gate_passed: assets.objects.success.clone(),
+            gate_missed: assets.objects.failure.clone(),
+            atlas: assets.atlas.clone(),
+            batches: vec![],
         }
     }
 
-    pub fn check_collision(&mut self, player: &Player) -> Option<CollisionAction> {
-        self.objects.iter_mut().find_map(|o| {
-            if o.hitbox().overlaps(&player.hitbox()) {
-                let action = o.collision_action;
-                o.collision_action = CollisionAction::Nothing;
-                Some(action)
-            } else {
-                None
+    /// Find an object the skier ran into that they haven't hit yet, and its
+    /// hitbox. Objects the skier is clear of can be hit again. Skiers are
+    /// numbered from 0 and there can be up to 32 of them.
+    pub fn check_collision(
+        &mut self,
+        player: &Player,
+        skier: usize,
+    ) -> Option<(ObjectKind, CollisionAction, Rect)> {
+        let hitbox = player.hitbox();
+        // a fallen skier's hitbox changes with their pose, without them
+        // going anywhere
+        let moving = player.direction().is_some();
+        let mut collision = None;
+        for o in &mut self.objects {
+            if o.collision_action == CollisionAction::Nothing {
+                continue;
             }
-        })
+            let object = o.hitbox();
+            if !object.overlaps(&hitbox) {
+                if moving {
+                    o.rearm(skier);
+                }
+            } else if collision.is_none() && o.hit(skier) {
+                collision = Some((o.kind, o.collision_action, object));
+            }
+        }
+        collision
     }
 
-    pub fn update(&mut self, assets: &Assets, player: &Player) {
-        let player_movement = player.opposite_direction().map(|d| (d, player.speed()));
-        // move everything in relation to the given movement_direction
-        // while checking which objects need to be cleared off top of screen
-        self.objects.retain_mut(|o| {
-            if o.position.y < -50. {
-                return false;
-            } else if let Some((direction, magnitude)) = player_movement {
-                o.shift(direction, magnitude);
-            }
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// What the snow is like at `position`
+    pub fn surface_at(&self, position: Vec2) -> Surface {
+        self.terrain.at(position)
+    }
+
+    /// Snow other than groomed in `view`, a rectangle of the map, moved by
+    /// `offset` from map to screen coordinates
+    pub fn visible_terrain(&self, view: Rect, offset: Vec2) -> Vec<(Surface, Rect)> {
+        let mut areas = self.terrain.visible(view);
+        for (_, area) in &mut areas {
+            area.translate(offset);
+        }
+        areas
+    }
+
+    /// Move the objects that move by themselves. Objects above the skiers
+    /// stay, as the chairlift takes them back up.
+    pub fn update(&mut self) {
+        for o in &mut self.objects {
             o.apply_movement();
-            true
-        });
+            o.animate();
+        }
+    }
+
+    /// Let the skier know about courses, gates and milestones they got to
+    pub fn update_progress(&mut self, progress: &mut Progress, skier: usize, player: &mut Player) {
+        let position = player.position();
+        let course = Course::at(position);
+        if course != progress.course {
+            progress.course = course;
+            if let Some(course) = course {
+                player.events().emit(GameEvent::CourseEntered(course));
+            }
+        }
+
+        let milestone = player.distance().max(0.) as u32 / MILESTONE_DISTANCE * MILESTONE_DISTANCE;
+        if milestone > progress.milestone {
+            progress.milestone = milestone;
+            player
+                .events()
+                .emit(GameEvent::DistanceMilestone(milestone));
+        }
 
-        for line in &mut self.lines {
-            if let Some((direction, magnitude)) = player_movement {
-                line.shift(direction, magnitude);
+        if course == Some(Course::Slalom) {
+            self.check_gates(progress, skier, player);
+        }
+        if let Some(course) = course {
+            if !progress.finished && position.y >= FINISH_Y as f32 {
+                progress.finished = true;
+                let missed_gates = match course {
+                    Course::Slalom => self.gate_count.saturating_sub(progress.gates_passed),
+                    Course::Freestyle | Course::TreeSlalom => 0,
+                };
+                player.events().emit(GameEvent::CourseFinished {
+                    course,
+                    missed_gates,
+                });
             }
         }
+    }
 
-        if let Some(direction) = player.direction() {
-            self.y_distance += vec2_from_angle(direction).y;
+    /// Score the gates the skier just went by
+    fn check_gates(&mut self, progress: &mut Progress, skier: usize, player: &mut Player) {
+        let player_box = player.hitbox();
+        let player_x = player_box.x + player_box.w / 2.;
+        for o in &mut self.objects {
+            let ObjectKind::Gate(gate) = &mut o.kind else {
+                continue;
+            };
+            if gate.result(skier).is_some() || o.position.y + o.image.height() as f32 > player_box.y
+            {
+                continue;
+            }
+            let gate_x = o.position.x + o.image.width() as f32 / 2.;
+            let passed = match gate.side {
+                GateSide::Left => player_x < gate_x,
+                GateSide::Right => player_x > gate_x,
+            };
+            gate.resolve(skier, passed);
+            if passed {
+                progress.gates_passed += 1;
+                player.events().emit(GameEvent::GatePassed);
+            } else {
+                player.events().emit(GameEvent::GateMissed);
+            }
         }
     }
 
-    pub fn draw(&self, ctx: &Context, canvas: &mut Canvas) {
-        for o in self.objects.iter().filter(|o| {
-            o.position.x >= 0. - 100.
-                && o.position.x <= WINDOW_WIDTH + 100.
-                && o.position.y >= 0. - 100.
-                && o.position.y <= WINDOW_HEIGHT + 100.
-        }) {
+    /// Objects in or near `view`, a rectangle of the map, with the sprite
+    /// `skier` sees them as and the offset to draw them at, from map to
+    /// screen coordinates and across the edge of the map
+    pub fn visible(
+        &self,
+        view: Rect,
+        offset: Vec2,
+        skier: usize,
+    ) -> impl Iterator<Item = (&Object, &Rc<Sprite>, Vec2)> {
+        self.objects.iter().filter_map(move |o| {
+            let x = wrap_x(o.position.x, view.center().x);
+            if x < view.left() - 100.
+                || x > view.right() + 100.
+                || o.position.y < view.top() - 100.
+                || o.position.y > view.bottom() + 100.
+            {
+                return None;
+            }
+            let shift = Vec2::new(x - o.position.x, 0.) + offset;
+            let image = match o.kind {
+                ObjectKind::Gate(gate) => match gate.result(skier) {
+                    Some(true) => &self.gate_passed,
+                    Some(false) => &self.gate_missed,
+                    None => &o.image,
+                },
+                _ => &o.image,
+            };
+            Some((o, image, shift))
+        })
+    }
+
+    pub fn lines(&self) -> &[LineObject] {
+        &self.lines
+    }
+
+    /// Draw what's in `view`, a rectangle of the map, as seen by `skier`.
+    /// `offset` moves map coordinates to screen coordinates.
+    pub fn draw(
+        &mut self,
+        ctx: &Context,
+        canvas: &mut Canvas,
+        view: Rect,
+        offset: Vec2,
+        skier: usize,
+    ) {
+        let terrain = self.visible_terrain(view, offset);
+        if !terrain.is_empty() {
+            let mut builder = MeshBuilder::new();
+            for (surface, area) in terrain {
+                let _ = builder.rectangle(DrawMode::fill(), area, surface.color());
+            }
+            canvas.draw(&Mesh::from_data(ctx, builder.build()), DrawParam::default());
+        }
+        // taken out while the objects are borrowed
+        let mut batches = std::mem::take(&mut self.batches);
+        while batches.len() <= skier {
+            batches.push(InstanceArray::new(ctx, self.atlas.clone()));
+        }
+        let batch = &mut batches[skier];
+        batch.clear();
+        for (o, image, shift) in self.visible(view, offset, skier) {
             #[cfg(debug_assertions)]
-            draw_hitbox(ctx, canvas, o.hitbox());
-            canvas.draw(o.image.as_ref(), o.position);
+            {
+                let mut hitbox = o.hitbox();
+                hitbox.translate(shift);
+                draw_hitbox(ctx, canvas, hitbox);
+            }
+            batch.push(image.param(DrawParam::default().dest(Vec2::from(o.position) + shift)));
         }
+        canvas.draw(batch, DrawParam::default());
+        self.batches = batches;
 
         for line in &self.lines {
-            line.draw(ctx, canvas);
+            line.draw(ctx, canvas, offset);
         }
     }
+}
+
+impl Progress {
+    pub fn course(&self) -> Option<Course> {
+        self.course
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// The map wraps around at its edges. Give the x coordinate of the copy of
+/// `x` closest to `near`.
+pub fn wrap_x(x: f32, near: f32) -> f32 {
+    let width = (MAP_WIDTH - MAP_X_START) as f32;
+    let mut x = x;
+    if x - near > width / 2. {
+        x -= width;
+    } else if near - x > width / 2. {
+        x += width;
+    }
+    x
+}
 
-    pub fn y_distance(&self) -> f32 {
-        self.y_distance
+/// Keep an x coordinate on the map, coming back on the other side when going
+/// over the edge
+pub fn wrap_around(x: f32) -> f32 {
+    let start = MAP_X_START as f32;
+    let end = MAP_WIDTH as f32;
+    if x > end {
+        start
+    } else if x < start {
+        end
+    } else {
+        x
     }
 }
 
@@ -160,7 +442,32 @@ fn starting_objects(assets: &Assets) -> Vec<Object> {
         (tree_slalom_xy, tree_slalom),
     ]
     .into_iter()
-    .map(|o| Object::immovable(o.0.into(), o.1, CollisionAction::Fall))
+    .map(|(xy, image)| {
+        let kind = if Rc::ptr_eq(image, btree) {
+            ObjectKind::BigTree
+        } else if Rc::ptr_eq(image, xtree) {
+            ObjectKind::DeadTree
+        } else {
+            ObjectKind::Sign
+        };
+        Object::immovable(kind, xy.into(), image, CollisionAction::Fall)
+    })
+    .chain([
+        Object::animated(
+            ObjectKind::Dog,
+            [150., 240.].into(),
+            assets,
+            &DOG_WAG,
+            CollisionAction::Nothing,
+        ),
+        Object::animated(
+            ObjectKind::Noob,
+            [340., 270.].into(),
+            assets,
+            &NOOB_FALL,
+            CollisionAction::Nothing,
+        ),
+    ])
     .collect()
 }
 
@@ -168,18 +475,21 @@ fn ski_lift(assets: &Assets) -> Vec<Object> {
     let mut objects = vec![];
     for y in (LIFT_Y_START..MAP_HEIGHT).step_by(400).map(|i| i as f32) {
         objects.push(Object::movable(
+            ObjectKind::Chairlift,
             [LIFT_X_POS - 18., y + 100.].into(),
             &assets.objects.chairlift,
-            CollisionAction::Nothing,
+            CollisionAction::Ride,
             |o| o.position.y += 0.2,
         ));
         objects.push(Object::movable(
+            ObjectKind::Chairlift,
             [LIFT_X_POS + 18., y + 400.].into(),
             &assets.objects.lifters,
             CollisionAction::Nothing,
             |o| o.position.y -= 0.2,
         ));
         objects.push(Object::immovable(
+            ObjectKind::LiftTower,
             [LIFT_X_POS, y].into(),
             &assets.objects.lift,
             CollisionAction::Fall,
@@ -188,7 +498,7 @@ fn ski_lift(assets: &Assets) -> Vec<Object> {
     objects
 }
 
-fn slalom_course(assets: &Assets, rng: &mut OsRng) -> Vec<Object> {
+fn slalom_course(assets: &Assets, rng: &mut StdRng, density: f64) -> Vec<Object> {
     let x_range = SLALOM_X_START..FREESTYLE_X_START;
     let x_spacing = 40;
 
@@ -199,23 +509,99 @@ fn slalom_course(assets: &Assets, rng: &mut OsRng) -> Vec<Object> {
     let mut objects = vec![];
     for y in y_iter {
         for x in x_range.clone().step_by(x_spacing).map(|x| x as f32) {
-            if rng.gen_bool(0.33) {
-                let (image, action) = match rng.gen_range(0..5) {
-                    0 => (&assets.objects.bump_l, CollisionAction::JumpSmall),
-                    1 => (&assets.objects.bump_s, CollisionAction::JumpSmall),
-                    2 => (&assets.objects.mogul, CollisionAction::JumpSmall),
-                    3 => (&assets.objects.rock, CollisionAction::Fall),
-                    4 => (&assets.objects.tree1, CollisionAction::Fall),
+            if rng.gen_bool(0.33 * density) {
+                let (kind, image, action) = match rng.gen_range(0..5) {
+                    0 => (
+                        ObjectKind::Bump,
+                        &assets.objects.bump_l,
+                        CollisionAction::JumpSmall,
+                    ),
+                    1 => (
+                        ObjectKind::Bump,
+                        &assets.objects.bump_s,
+                        CollisionAction::JumpSmall,
+                    ),
+                    2 => (
+                        ObjectKind::Mogul,
+                        &assets.objects.mogul,
+                        CollisionAction::Hop,
+                    ),
+                    3 => (
+                        ObjectKind::Rock,
+                        &assets.objects.rock,
+                        CollisionAction::Deflect,
+                    ),
+                    4 => (
+                        ObjectKind::Tree,
+                        &assets.objects.tree1,
+                        CollisionAction::Fall,
+                    ),
                     _ => continue,
                 };
-                objects.push(Object::immovable([x, y].into(), image, action))
+                objects.push(Object::immovable(kind, [x, y].into(), image, action))
             }
         }
     }
     objects
 }
 
-fn freestyle_course(assets: &Assets, rng: &mut OsRng) -> Vec<Object> {
+fn slalom_gates(assets: &Assets) -> Vec<Object> {
+    let center = (SLALOM_X_START + FREESTYLE_X_START) as f32 / 2.;
+    let first_gate = COURSE_Y_START + 200;
+    let mut objects = vec![
+        Object::immovable(
+            ObjectKind::Sign,
+            [center - 100., first_gate as f32 - 100.].into(),
+            &assets.objects.start_l,
+            CollisionAction::Fall,
+        ),
+        Object::immovable(
+            ObjectKind::Sign,
+            [center + 60., first_gate as f32 - 100.].into(),
+            &assets.objects.start_r,
+            CollisionAction::Fall,
+        ),
+    ];
+    // flags either side of the middle, each to be gone around on its far
+    // side, so the skier has to zig-zag
+    for (i, y) in (first_gate..FINISH_Y).step_by(GATE_SPACING).enumerate() {
+        let (side, image, x) = if i % 2 == 0 {
+            (GateSide::Left, &assets.objects.slol_l, center - 60.)
+        } else {
+            (GateSide::Right, &assets.objects.slol_r, center + 60.)
+        };
+        let gate = Gate::new(side);
+        objects.push(Object::immovable(
+            ObjectKind::Gate(gate),
+            [x, y as f32].into(),
+            image,
+            CollisionAction::Nothing,
+        ));
+    }
+    objects
+}
+
+/// Banners either side of the middle of the course at `FINISH_Y`
+fn finish_line(assets: &Assets, course: Course) -> [Object; 2] {
+    let range = course.x_range();
+    let center = (range.start + range.end) as f32 / 2.;
+    [
+        Object::immovable(
+            ObjectKind::Sign,
+            [center - 100., FINISH_Y as f32].into(),
+            &assets.objects.finish_l,
+            CollisionAction::Fall,
+        ),
+        Object::immovable(
+            ObjectKind::Sign,
+            [center + 60., FINISH_Y as f32].into(),
+            &assets.objects.finish_r,
+            CollisionAction::Fall,
+        ),
+    ]
+}
+
+fn freestyle_course(assets: &Assets, rng: &mut StdRng, density: f64) -> Vec<Object> {
     let x_range = FREESTYLE_X_START..TREE_SLALOM_X_START;
     let x_spacing = 40;
 
@@ -226,26 +612,58 @@ fn freestyle_course(assets: &Assets, rng: &mut OsRng) -> Vec<Object> {
     let mut objects = vec![];
     for y in y_iter {
         for x in x_range.clone().step_by(x_spacing).map(|x| x as f32) {
-            if rng.gen_bool(0.30) {
-                let (image, action) = match rng.gen_range(0..14) {
-                    0..=2 => (&assets.objects.bump_l, CollisionAction::JumpSmall),
-                    3..=5 => (&assets.objects.bump_s, CollisionAction::JumpSmall),
-                    6..=8 => (&assets.objects.ramp, CollisionAction::JumpLarge),
-                    9 => (&assets.objects.rock, CollisionAction::Fall),
-                    10 => (&assets.objects.tree1, CollisionAction::Fall),
-                    11 => (&assets.objects.stump, CollisionAction::Fall),
-                    12 => (&assets.objects.xtree1, CollisionAction::Fall),
-                    13 => (&assets.objects.xtree2, CollisionAction::Fall),
+            if rng.gen_bool(0.30 * density) {
+                let (kind, image, action) = match rng.gen_range(0..14) {
+                    0..=2 => (
+                        ObjectKind::Bump,
+                        &assets.objects.bump_l,
+                        CollisionAction::JumpSmall,
+                    ),
+                    3..=5 => (
+                        ObjectKind::Bump,
+                        &assets.objects.bump_s,
+                        CollisionAction::JumpSmall,
+                    ),
+                    6..=8 => (
+                        ObjectKind::Ramp,
+                        &assets.objects.ramp,
+                        CollisionAction::JumpLarge,
+                    ),
+                    9 => (
+                        ObjectKind::Rock,
+                        &assets.objects.rock,
+                        CollisionAction::Deflect,
+                    ),
+                    10 => (
+                        ObjectKind::Tree,
+                        &assets.objects.tree1,
+                        CollisionAction::Fall,
+                    ),
+                    11 => (
+                        ObjectKind::Stump,
+                        &assets.objects.stump,
+                        CollisionAction::Fall,
+                    ),
+                    12 => (
+                        ObjectKind::DeadTree,
+                        &assets.objects.xtree1,
+                        CollisionAction::Fall,
+                    ),
+                    13 => (
+                        ObjectKind::DeadTree,
+                        &assets.objects.xtree2,
+                        CollisionAction::Fall,
+                    ),
                     _ => continue,
                 };
-                objects.push(Object::immovable([x, y].into(), image, action))
+                objects.push(Object::immovable(kind, [x, y].into(), image, action))
             }
         }
     }
     objects
 }
 
-fn tree_slalom_course(assets: &Assets, rng: &mut OsRng) -> Vec<Object> {
+fn tree_slalom_course(assets: &Assets, rng: &mut StdRng, density: f64) -> Vec<Object> {
     let x_range = TREE_SLALOM_X_START..MAP_WIDTH;
     let x_spacing = 40;
 
@@ -256,24 +674,163 @@ fn tree_slalom_course(assets: &Assets, rng: &mut OsRng) -> Vec<Object> {
     let mut objects = vec![];
     for y in y_iter {
         for x in x_range.clone().step_by(x_spacing).map(|x| x as f32) {
-            if rng.gen_bool(0.30) {
-                let (image, action) = match rng.gen_range(0..15) {
-                    0..=4 => (&assets.objects.bigtree, CollisionAction::Fall),
-                    5 => (&assets.objects.tree1, CollisionAction::Fall),
-                    6 => (&assets.objects.tree2, CollisionAction::Fall),
-                    7 => (&assets.objects.tree3, CollisionAction::Fall),
-                    8 => (&assets.objects.tree4, CollisionAction::Fall),
-                    9 => (&assets.objects.xtree1, CollisionAction::Fall),
-                    10 => (&assets.objects.xtree2, CollisionAction::Fall),
-                    11 => (&assets.objects.xtree3, CollisionAction::Fall),
-                    12 => (&assets.objects.stump, CollisionAction::Fall),
-                    13 => (&assets.objects.mushroom, CollisionAction::Nothing),
-                    14 => (&assets.objects.rock, CollisionAction::Fall),
+            if rng.gen_bool(0.30 * density) {
+                let (kind, image, action) = match rng.gen_range(0..15) {
+                    0..=4 => (
+                        ObjectKind::BigTree,
+                        &assets.objects.bigtree,
+                        CollisionAction::Fall,
+                    ),
+                    5 => (
+                        ObjectKind::Tree,
+                        &assets.objects.tree1,
+                        CollisionAction::Fall,
+                    ),
+                    6 => (
+                        ObjectKind::Tree,
+                        &assets.objects.tree2,
+                        CollisionAction::Fall,
+                    ),
+                    7 => (
+                        ObjectKind::Tree,
+                        &assets.objects.tree3,
+                        CollisionAction::Fall,
+                    ),
+                    8 => (
+                        ObjectKind::Tree,
+                        &assets.objects.tree4,
+                        CollisionAction::Fall,
+                    ),
+                    9 => (
+                        ObjectKind::DeadTree,
+                        &assets.objects.xtree1,
+                        CollisionAction::Fall,
+                    ),
+                    10 => (
+                        ObjectKind::DeadTree,
+                        &assets.objects.xtree2,
+                        CollisionAction::Fall,
+                    ),
+                    11 => (
+                        ObjectKind::DeadTree,
+                        &assets.objects.xtree3,
+                        CollisionAction::Fall,
+                    ),
+                    12 => (
+                        ObjectKind::Stump,
+                        &assets.objects.stump,
+                        CollisionAction::Fall,
+                    ),
+                    13 => (
+                        ObjectKind::Mushroom,
+                        &assets.objects.mushroom,
+                        CollisionAction::Nothing,
+                    ),
+                    14 => (
+                        ObjectKind::Rock,
+                        &assets.objects.rock,
+                        CollisionAction::Deflect,
+                    ),
                     _ => continue,
                 };
-                objects.push(Object::immovable([x, y].into(), image, action))
+                objects.push(Object::immovable(kind, [x, y].into(), image, action))
             }
         }
     }
     objects
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Simulation;
+
+    #[test]
+    fn can_find_course_at_position() {
+        assert_eq!(Course::at([0., 0.].into()), None);
+        assert_eq!(Course::at([-1000., 500.].into()), Some(Course::Slalom));
+        assert_eq!(Course::at([0., 500.].into()), Some(Course::Freestyle));
+        assert_eq!(Course::at([1000., 500.].into()), Some(Course::TreeSlalom));
+    }
+
+    /// A slope with one tree left on it, and a skier heading straight down
+    fn lone_tree() -> (Map, Player) {
+        let assets = Assets::headless();
+        let mut map = Map::new(&assets, Difficulty::Easy, 1);
+        map.objects.retain(|o| o.kind == ObjectKind::Tree);
+        map.objects.truncate(1);
+        let mut player = Player::new(&assets);
+        player.steer(Some(0.));
+        player.maybe_next_state(&assets);
+        (map, player)
+    }
+
+    /// Move the skier so the middle of their feet is on `spot`
+    fn stand_on(player: &mut Player, spot: Vec2) {
+        let feet = Vec2::from(player.hitbox().center());
+        player.place(player.position() + spot - feet);
+    }
+
+    #[test]
+    fn cleared_objects_can_be_hit_again() {
+        let (mut map, mut player) = lone_tree();
+        let tree = Vec2::from(map.objects[0].hitbox().center());
+        stand_on(&mut player, tree);
+        assert!(map.check_collision(&player, 0).is_some());
+        // still on the way through
+        assert!(map.check_collision(&player, 0).is_none());
+        stand_on(&mut player, tree + Vec2::new(0., 100.));
+        assert!(map.check_collision(&player, 0).is_none());
+        stand_on(&mut player, tree);
+        assert!(map.check_collision(&player, 0).is_some());
+        // other skiers are tracked on their own
+        assert!(map.check_collision(&player, 1).is_some());
+    }
+
+    #[test]
+    fn fallen_skiers_dont_hit_again() {
+        let (mut map, mut player) = lone_tree();
+        let tree = Vec2::from(map.objects[0].hitbox().center());
+        stand_on(&mut player, tree);
+        let (kind, action, hitbox) = map.check_collision(&player, 0).unwrap();
+        player.collision(kind, action, hitbox);
+        assert_eq!(player.direction(), None, "knocked over");
+        // as if the fallen pose's hitbox came off the tree and back on
+        stand_on(&mut player, tree + Vec2::new(0., 100.));
+        assert!(map.check_collision(&player, 0).is_none());
+        stand_on(&mut player, tree);
+        assert!(map.check_collision(&player, 0).is_none());
+    }
+
+    #[test]
+    fn objects_stay_for_the_ride_back_up() {
+        let mut sim = Simulation::new(1, Difficulty::Easy);
+        let count = sim.map.objects().len();
+        sim.skier.player.steer(Some(0.));
+        while sim.tick() < 600 {
+            sim.step();
+        }
+        assert!(sim.skier.player.position().y > Player::POSITION[1] + 1000.);
+        assert_eq!(sim.map.objects().len(), count);
+    }
+
+    #[test]
+    fn straight_down_the_slalom_misses_the_gates() {
+        let mut sim = Simulation::new(1, Difficulty::Easy);
+        // nothing to knock the skier off their line
+        sim.map
+            .objects
+            .retain(|o| matches!(o.kind, ObjectKind::Gate(_)));
+        sim.skier.player.place(Course::Slalom.start());
+        sim.skier.player.steer(Some(0.));
+        let mut gates = vec![];
+        while sim.tick() < 1000 && gates.len() < 4 {
+            gates.extend(
+                sim.step()
+                    .into_iter()
+                    .filter(|e| matches!(e, GameEvent::GatePassed | GameEvent::GateMissed)),
+            );
+        }
+        assert_eq!(gates, vec![GameEvent::GateMissed; 4]);
+    }
+}
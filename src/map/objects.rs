@@ -1,23 +1,112 @@
 use std::rc::Rc;
 
 use ggez::glam::Vec2;
-use ggez::graphics::{Canvas, Color, DrawParam, Image, Mesh, Rect};
+use ggez::graphics::{Canvas, Color, DrawParam, Mesh, Rect};
 use ggez::mint::Point2;
 use ggez::Context;
 
-use crate::map::{MAP_WIDTH, MAP_X_START};
+use crate::animation::{Animation, Clip, SpriteRef};
+use crate::assets::{Assets, Sprite};
 use crate::player::CollisionAction;
-use crate::util::vec2_from_angle;
 
-pub trait Shift {
-    fn shift(&mut self, direction: f32, magnitude: f32);
+/// What an object on the map is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+    Tree,
+    BigTree,
+    DeadTree,
+    Rock,
+    Stump,
+    Mushroom,
+    Bump,
+    Mogul,
+    Ramp,
+    LiftTower,
+    Chairlift,
+    Sign,
+    Dog,
+    Noob,
+    Gate(Gate),
+}
+
+impl ObjectKind {
+    /// Lowercase name of the kind, as telemetry logs nearby obstacles
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectKind::Tree => "tree",
+            ObjectKind::BigTree => "big_tree",
+            ObjectKind::DeadTree => "dead_tree",
+            ObjectKind::Rock => "rock",
+            ObjectKind::Stump => "stump",
+            ObjectKind::Mushroom => "mushroom",
+            ObjectKind::Bump => "bump",
+            ObjectKind::Mogul => "mogul",
+            ObjectKind::Ramp => "ramp",
+            ObjectKind::LiftTower => "lift_tower",
+            ObjectKind::Chairlift => "chairlift",
+            ObjectKind::Sign => "sign",
+            ObjectKind::Dog => "dog",
+            ObjectKind::Noob => "noob",
+            ObjectKind::Gate(_) => "gate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateSide {
+    Left,
+    Right,
+}
+
+/// Slalom flag. The skier has to pass on the side the flag points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gate {
+    pub side: GateSide,
+    /// Skiers that went around the correct side, one bit per skier
+    pub passed_by: u32,
+    /// Skiers that went around the wrong side
+    pub missed_by: u32,
+}
+
+impl Gate {
+    pub fn new(side: GateSide) -> Self {
+        Self {
+            side,
+            passed_by: 0,
+            missed_by: 0,
+        }
+    }
+
+    /// Whether the skier went around the correct side, once they've gone by
+    pub fn result(&self, skier: usize) -> Option<bool> {
+        let bit = 1 << skier;
+        if self.passed_by & bit != 0 {
+            Some(true)
+        } else if self.missed_by & bit != 0 {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn resolve(&mut self, skier: usize, passed: bool) {
+        if passed {
+            self.passed_by |= 1 << skier;
+        } else {
+            self.missed_by |= 1 << skier;
+        }
+    }
 }
 
 pub struct Object {
+    pub kind: ObjectKind,
     pub position: Point2<f32>,
-    pub image: Rc<Image>,
+    pub image: Rc<Sprite>,
     pub collision_action: CollisionAction,
+    /// Skiers that already ran into the object, one bit per skier
+    hit_by: u32,
     movement: Option<fn(&mut Self)>,
+    animation: Option<Animation>,
 }
 
 pub struct LineObject {
@@ -35,82 +124,99 @@ impl LineObject {
         }
     }
 
-    pub fn draw(&self, ctx: &Context, canvas: &mut Canvas) {
+    /// Draw the line moved by `offset`, from map to screen coordinates
+    pub fn draw(&self, ctx: &Context, canvas: &mut Canvas, offset: Vec2) {
         canvas.draw(
             &Mesh::new_line(ctx, &self.points, self.width, self.color).unwrap(),
-            DrawParam::default(),
+            DrawParam::default().dest(offset),
         )
     }
 }
 
 impl Object {
     pub fn immovable(
+        kind: ObjectKind,
         position: Point2<f32>,
-        image: &Rc<Image>,
+        image: &Rc<Sprite>,
         collision_action: CollisionAction,
     ) -> Self {
-        Self::new(position, image, collision_action, None)
+        Self::new(kind, position, image, collision_action, None)
     }
 
     pub fn movable(
+        kind: ObjectKind,
         position: Point2<f32>,
-        image: &Rc<Image>,
+        image: &Rc<Sprite>,
         collision_action: CollisionAction,
         movement: fn(&mut Self),
     ) -> Self {
-        Self::new(position, image, collision_action, Some(movement))
+        Self::new(kind, position, image, collision_action, Some(movement))
+    }
+
+    /// An object that stays put and plays `clip` over and over
+    pub fn animated(
+        kind: ObjectKind,
+        position: Point2<f32>,
+        assets: &Assets,
+        clip: &'static Clip<SpriteRef>,
+        collision_action: CollisionAction,
+    ) -> Self {
+        let animation = Animation::new(clip, assets);
+        Self {
+            animation: Some(animation.clone()),
+            ..Self::new(kind, position, animation.sprite(), collision_action, None)
+        }
     }
 
     fn new(
+        kind: ObjectKind,
         position: Point2<f32>,
-        image: &Rc<Image>,
+        image: &Rc<Sprite>,
         collision_action: CollisionAction,
         movement: Option<fn(&mut Self)>,
     ) -> Self {
         Self {
+            kind,
             position,
             image: image.clone(),
             collision_action,
+            hit_by: 0,
             movement,
+            animation: None,
         }
     }
 
+    /// Mark the object as hit by the skier. Returns false if it already was,
+    /// so a skier only collides with an object once on the way through.
+    pub fn hit(&mut self, skier: usize) -> bool {
+        let bit = 1 << skier;
+        let first = self.hit_by & bit == 0;
+        self.hit_by |= bit;
+        first
+    }
+
+    /// Let the skier hit the object again, once they're clear of it
+    pub fn rearm(&mut self, skier: usize) {
+        self.hit_by &= !(1 << skier);
+    }
+
     pub fn apply_movement(&mut self) {
         if let Some(f) = self.movement {
             f(self)
         }
     }
 
+    /// Move the animation on a tick
+    pub fn animate(&mut self) {
+        if let Some(animation) = &mut self.animation {
+            animation.update();
+            self.image = animation.sprite().clone();
+        }
+    }
+
     pub fn hitbox(&self) -> Rect {
         let width = self.image.width() as f32;
         let height = self.image.height() as f32;
         Rect::new(self.position.x, self.position.y + height, width, 5.)
     }
 }
-
-impl Shift for Object {
-    fn shift(&mut self, direction: f32, magnitude: f32) {
-        let mut pos: Vec2 = self.position.into();
-        let v2 = vec2_from_angle(direction);
-        pos += v2 * magnitude;
-        let start = MAP_X_START as f32;
-        let end = MAP_WIDTH as f32;
-        if pos.x > end {
-            pos.x = start;
-        } else if pos.x < start {
-            pos.x = end;
-        }
-        self.position = pos.into();
-    }
-}
-
-impl Shift for LineObject {
-    fn shift(&mut self, direction: f32, magnitude: f32) {
-        self.points = self.points.map(|v| {
-            let v2 = vec2_from_angle(direction);
-            let new: Vec2 = v2 * magnitude;
-            let v: Vec2 = v.into();
-            (v + new).into()
-        });
-    }
-}
@@ -0,0 +1,280 @@
+//! What the snow is like all over the map, kept as a grid of tiles under
+//! the objects
+
+use ggez::glam::Vec2;
+use ggez::graphics::{Color, Rect};
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::{Course, COURSE_Y_START, FINISH_Y, MAP_HEIGHT, MAP_WIDTH, MAP_X_START};
+
+/// Pixels a side of a tile
+pub const TILE_SIZE: i32 = 20;
+/// Room between one patch and the next down a course
+const PATCH_SPACING: i32 = 600;
+/// Groomed snow past the finish line to stop in, before the backcountry
+const RUNOUT: i32 = 400;
+
+/// What the snow under the skier is like
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Surface {
+    #[default]
+    Groomed,
+    /// Deep and slow, and turns take a moment to bite
+    Powder,
+    /// Fast, but turns slide out
+    Ice,
+    /// Wet and heavy, slow but easy to turn in
+    Slush,
+    /// Past the end of the courses, ungroomed and slow going
+    OutOfBounds,
+}
+
+impl Surface {
+    /// What the snow underfoot is logged as
+    pub fn name(&self) -> &'static str {
+        match self {
+            Surface::Groomed => "groomed",
+            Surface::Powder => "powder",
+            Surface::Ice => "ice",
+            Surface::Slush => "slush",
+            Surface::OutOfBounds => "out_of_bounds",
+        }
+    }
+
+    /// How fast the skier goes on it, compared to groomed snow
+    pub fn speed(&self) -> f32 {
+        match self {
+            Surface::Groomed => 1.,
+            Surface::Powder => 0.6,
+            Surface::Ice => 1.3,
+            Surface::Slush => 0.75,
+            Surface::OutOfBounds => 0.5,
+        }
+    }
+
+    /// How much of the way to where the skier is facing they get each
+    /// tick, from 0, never, to 1, at once
+    pub fn grip(&self) -> f32 {
+        match self {
+            Surface::Groomed | Surface::Slush => 1.,
+            Surface::Powder => 0.3,
+            Surface::Ice => 0.05,
+            Surface::OutOfBounds => 0.4,
+        }
+    }
+
+    /// What the snow is drawn as. Groomed snow is the white of the slope.
+    pub fn color(&self) -> Color {
+        match self {
+            Surface::Groomed => Color::WHITE,
+            Surface::Powder => Color::new(0.94, 0.95, 0.98, 1.),
+            Surface::Ice => Color::new(0.85, 0.93, 1., 1.),
+            Surface::Slush => Color::new(0.95, 0.94, 0.9, 1.),
+            Surface::OutOfBounds => Color::new(0.91, 0.92, 0.9, 1.),
+        }
+    }
+}
+
+/// Surfaces of the map, a row of tiles at a time from the top left
+pub struct Terrain {
+    tiles: Vec<Surface>,
+    cols: i32,
+    rows: i32,
+}
+
+impl Terrain {
+    /// Groom the courses, leaving the backcountry past their ends and off
+    /// their sides, and scatter patches of ice, powder and slush down them.
+    /// There's more of it the more obstacles there are, and slush lower
+    /// down.
+    pub fn generate(rng: &mut StdRng, density: f64) -> Self {
+        let cols = (MAP_WIDTH - MAP_X_START) / TILE_SIZE;
+        let rows = MAP_HEIGHT / TILE_SIZE;
+        let mut terrain = Self {
+            tiles: vec![Surface::Groomed; (cols * rows) as usize],
+            cols,
+            rows,
+        };
+        let backcountry = (FINISH_Y + RUNOUT) / TILE_SIZE;
+        terrain.tiles[(backcountry * cols) as usize..].fill(Surface::OutOfBounds);
+        // the courses side by side are as wide as the map, so this only
+        // matters if they stop being
+        for row in COURSE_Y_START / TILE_SIZE..backcountry {
+            for col in 0..cols {
+                if Course::at(Self::middle(row, col)).is_none() {
+                    terrain.tiles[(row * cols + col) as usize] = Surface::OutOfBounds;
+                }
+            }
+        }
+
+        for course in Course::ALL {
+            let range = course.x_range();
+            for y in (COURSE_Y_START + PATCH_SPACING..FINISH_Y).step_by(PATCH_SPACING as usize) {
+                if !rng.gen_bool((0.4 * density).min(1.)) {
+                    continue;
+                }
+                let surface = match (rng.gen_range(0..3), y > FINISH_Y / 2) {
+                    (0, _) => Surface::Ice,
+                    (_, false) => Surface::Powder,
+                    (_, true) => Surface::Slush,
+                };
+                let radius =
+                    Vec2::new(rng.gen_range(60..180) as f32, rng.gen_range(40..120) as f32);
+                let center = Vec2::new(
+                    rng.gen_range(range.start + radius.x as i32..range.end - radius.x as i32)
+                        as f32,
+                    (y + rng.gen_range(radius.y as i32..PATCH_SPACING - radius.y as i32)) as f32,
+                );
+                terrain.fill_ellipse(center, radius, surface);
+            }
+        }
+        terrain
+    }
+
+    /// Cover the tiles whose middles are in the ellipse
+    fn fill_ellipse(&mut self, center: Vec2, radius: Vec2, surface: Surface) {
+        let (top_left, bottom_right) = (center - radius, center + radius);
+        for row in self.row(top_left.y)..=self.row(bottom_right.y) {
+            for col in self.col(top_left.x)..=self.col(bottom_right.x) {
+                let middle = Self::middle(row, col);
+                if ((middle - center) / radius).length_squared() <= 1. {
+                    self.tiles[(row * self.cols + col) as usize] = surface;
+                }
+            }
+        }
+    }
+
+    /// Map position of the middle of a tile
+    fn middle(row: i32, col: i32) -> Vec2 {
+        Vec2::new(
+            ((col as f32 + 0.5) * TILE_SIZE as f32) + MAP_X_START as f32,
+            (row as f32 + 0.5) * TILE_SIZE as f32,
+        )
+    }
+
+    fn col(&self, x: f32) -> i32 {
+        (x.floor() as i32 - MAP_X_START)
+            .div_euclid(TILE_SIZE)
+            .rem_euclid(self.cols)
+    }
+
+    fn row(&self, y: f32) -> i32 {
+        (y.floor() as i32)
+            .div_euclid(TILE_SIZE)
+            .clamp(0, self.rows - 1)
+    }
+
+    /// The surface at a map position. Above the top of the map is groomed
+    /// and below the bottom is backcountry.
+    pub fn at(&self, position: Vec2) -> Surface {
+        if position.y < 0. {
+            return Surface::Groomed;
+        }
+        let row = position.y.floor() as i32 / TILE_SIZE;
+        if row >= self.rows {
+            return Surface::OutOfBounds;
+        }
+        self.tiles[(row * self.cols + self.col(position.x)) as usize]
+    }
+
+    /// Every surface other than groomed snow in `view`, a rectangle of the
+    /// map, as areas in the same coordinates, even across the edge of the
+    /// map. Tiles next to each other in a row come as one area.
+    pub fn visible(&self, view: Rect) -> Vec<(Surface, Rect)> {
+        let mut areas = vec![];
+        let tile = TILE_SIZE as f32;
+        let first_col = (view.left() / tile).floor() as i32;
+        let last_col = (view.right() / tile).floor() as i32;
+        let first_row = (view.top() / tile).floor() as i32;
+        let last_row = (view.bottom() / tile).floor() as i32;
+        let at = |col: i32, y: f32| self.at(Vec2::new(col as f32 * tile, y));
+        for row in first_row..=last_row {
+            let y = row as f32 * tile;
+            let (mut start, mut current) = (first_col, at(first_col, y));
+            for col in first_col + 1..=last_col + 1 {
+                let next = (col <= last_col).then(|| at(col, y));
+                if next == Some(current) {
+                    continue;
+                }
+                if current != Surface::Groomed {
+                    let width = (col - start) as f32 * tile;
+                    areas.push((current, Rect::new(start as f32 * tile, y, width, tile)));
+                }
+                if let Some(next) = next {
+                    (start, current) = (col, next);
+                }
+            }
+        }
+        areas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn patches_wrap_around_the_map() {
+        let terrain = Terrain::generate(&mut StdRng::seed_from_u64(1), 1.);
+        assert_eq!(terrain.at(Vec2::new(0., 100.)), Surface::Groomed);
+        let width = (MAP_WIDTH - MAP_X_START) as f32;
+        for x in (MAP_X_START..MAP_WIDTH).step_by(37).map(|x| x as f32) {
+            for y in (0..FINISH_Y).step_by(53).map(|y| y as f32) {
+                let position = Vec2::new(x, y);
+                let wrapped = Vec2::new(x + width, y);
+                assert_eq!(terrain.at(position), terrain.at(wrapped));
+            }
+        }
+        for surface in [Surface::Ice, Surface::Powder, Surface::Slush] {
+            assert!(terrain.tiles.contains(&surface), "no {surface:?}");
+        }
+    }
+
+    #[test]
+    fn backcountry_past_the_finish() {
+        let terrain = Terrain::generate(&mut StdRng::seed_from_u64(1), 1.);
+        let runout = (FINISH_Y + RUNOUT / 2) as f32;
+        assert_eq!(terrain.at(Vec2::new(0., runout)), Surface::Groomed);
+        let past = (FINISH_Y + RUNOUT + TILE_SIZE) as f32;
+        assert_eq!(terrain.at(Vec2::new(0., past)), Surface::OutOfBounds);
+        assert_eq!(terrain.at(Vec2::new(0., 1e6)), Surface::OutOfBounds);
+    }
+
+    #[test]
+    fn courses_leave_no_backcountry_at_the_sides() {
+        let terrain = Terrain::generate(&mut StdRng::seed_from_u64(1), 1.);
+        let y = (COURSE_Y_START + FINISH_Y) as f32 / 2.;
+        for x in (MAP_X_START..MAP_WIDTH).step_by(TILE_SIZE as usize) {
+            let position = Vec2::new(x as f32, y);
+            assert!(Course::at(position).is_some(), "{x} is on no course");
+            assert_ne!(terrain.at(position), Surface::OutOfBounds);
+        }
+    }
+
+    #[test]
+    fn visible_areas_match_the_tiles() {
+        let terrain = Terrain::generate(&mut StdRng::seed_from_u64(2), 1.);
+        let first = terrain
+            .tiles
+            .iter()
+            .position(|s| *s != Surface::Groomed)
+            .unwrap() as i32;
+        let tile = Vec2::new(
+            (first % terrain.cols * TILE_SIZE + MAP_X_START) as f32,
+            (first / terrain.cols * TILE_SIZE) as f32,
+        );
+        let view = Rect::new(tile.x - 240., tile.y - 320., 480., 640.);
+        let areas = terrain.visible(view);
+        assert!(!areas.is_empty());
+        for (surface, area) in areas {
+            assert_ne!(surface, Surface::Groomed);
+            for x in (0..area.w as i32).step_by(TILE_SIZE as usize) {
+                let middle = Vec2::new(area.x + x as f32 + 10., area.y + 10.);
+                assert_eq!(terrain.at(middle), surface);
+            }
+        }
+    }
+}
@@ -0,0 +1,609 @@
+use ggez::event::Button;
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, Mesh, PxScale, Rect, Text};
+use ggez::winit::event::VirtualKeyCode;
+use ggez::Context;
+
+use crate::config::Config;
+use crate::input::{Action, Bindings, Input, Thumbstick};
+use crate::settings::{Settings, Skin};
+use crate::util::screen_size;
+
+/// What the game should do after the menu handled a key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEvent {
+    Resume,
+    Restart,
+    /// Restart on a freshly generated slope
+    NewSlope,
+    Quit,
+    SettingsChanged,
+    BindingsChanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screen {
+    Pause,
+    Options,
+    /// Bindings of a skier, counting from 0
+    KeyBindings(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Resume,
+    Restart,
+    NewSlope,
+    Options,
+    Quit,
+    Players,
+    KeyBindings,
+    PlayerTwoKeys,
+    Difficulty,
+    Volume,
+    MusicVolume,
+    Fullscreen,
+    Minimap,
+    Skin,
+    Bind(Action),
+    ResetBindings,
+    Back,
+}
+
+const KEY_BINDING_ITEMS: [Item; 12] = [
+    Item::Bind(Action::SteerLeft),
+    Item::Bind(Action::SteerRight),
+    Item::Bind(Action::Steer),
+    Item::Bind(Action::Trick1),
+    Item::Bind(Action::Trick2),
+    Item::Bind(Action::Flip),
+    Item::Bind(Action::Boost),
+    Item::Bind(Action::Pause),
+    Item::Bind(Action::Quit),
+    Item::Bind(Action::ToggleMinimap),
+    Item::ResetBindings,
+    Item::Back,
+];
+
+const PLAYER_TWO_ITEMS: [Item; 9] = [
+    Item::Bind(Action::SteerLeft),
+    Item::Bind(Action::SteerRight),
+    Item::Bind(Action::Steer),
+    Item::Bind(Action::Trick1),
+    Item::Bind(Action::Trick2),
+    Item::Bind(Action::Flip),
+    Item::Bind(Action::Boost),
+    Item::ResetBindings,
+    Item::Back,
+];
+
+impl Screen {
+    /// Items on the screen. Player 2's keys only show with two players.
+    fn items(&self, settings: &Settings) -> Vec<Item> {
+        let items: &[Item] = match self {
+            Screen::Pause => &[
+                Item::Resume,
+                Item::Restart,
+                Item::NewSlope,
+                Item::Players,
+                Item::Options,
+                Item::Quit,
+            ],
+            Screen::Options => &[
+                Item::KeyBindings,
+                Item::PlayerTwoKeys,
+                Item::Difficulty,
+                Item::Volume,
+                Item::MusicVolume,
+                Item::Fullscreen,
+                Item::Minimap,
+                Item::Skin,
+                Item::Back,
+            ],
+            Screen::KeyBindings(0) => &KEY_BINDING_ITEMS,
+            Screen::KeyBindings(_) => &PLAYER_TWO_ITEMS,
+        };
+        items
+            .iter()
+            .copied()
+            .filter(|item| *item != Item::PlayerTwoKeys || settings.players > 1)
+            .collect()
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Screen::Pause => "Paused",
+            Screen::Options => "Options",
+            Screen::KeyBindings(0) => "Key Bindings",
+            Screen::KeyBindings(_) => "Player 2 Keys",
+        }
+    }
+}
+
+impl Item {
+    /// Text for the item. Bindings are those of `skier`.
+    fn label(&self, config: &Config, skier: usize) -> String {
+        let settings = &config.settings;
+        let on_off = |b: bool| if b { "on" } else { "off" };
+        match self {
+            Item::Resume => "Resume".into(),
+            Item::Restart => "Restart".into(),
+            Item::NewSlope => "New slope".into(),
+            Item::Options => "Options".into(),
+            Item::Quit => "Quit".into(),
+            Item::Players => format!("Players: < {} >", settings.players),
+            Item::KeyBindings => "Key bindings".into(),
+            Item::PlayerTwoKeys => "Player 2 keys".into(),
+            Item::Difficulty => format!("Difficulty: < {} >", settings.difficulty.name()),
+            Item::Volume => format!("Volume: < {} >", settings.volume),
+            Item::MusicVolume => format!("Music: < {} >", settings.music_volume),
+            Item::Fullscreen => format!("Fullscreen: {}", on_off(settings.fullscreen)),
+            Item::Minimap => format!("Minimap: {}", on_off(settings.show_minimap)),
+            Item::Skin => format!("Skier: < {} >", settings.skin.name()),
+            Item::Bind(action) => {
+                let bindings = config.bindings(skier);
+                let inputs = bindings.inputs(*action);
+                let inputs = if inputs.is_empty() {
+                    "-".to_string()
+                } else {
+                    inputs
+                        .iter()
+                        .map(|i| i.name().unwrap_or("?"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                let shared = match settings.players {
+                    1 => vec![],
+                    _ => config.shared_inputs(),
+                };
+                let conflict = if bindings.is_conflicting(*action)
+                    || bindings.inputs(*action).iter().any(|i| shared.contains(i))
+                {
+                    " (!)"
+                } else {
+                    ""
+                };
+                format!("{:<12}{inputs}{conflict}", action.name())
+            }
+            Item::ResetBindings => "Reset to defaults".into(),
+            Item::Back => "Back".into(),
+        }
+    }
+}
+
+pub struct Menu {
+    screen: Screen,
+    selected: usize,
+    /// Action waiting for a key press to be bound to it
+    capturing: Option<Action>,
+    /// Skins that have been unlocked
+    skins: Vec<Skin>,
+}
+
+impl Menu {
+    pub fn pause(skins: Vec<Skin>) -> Self {
+        Self {
+            screen: Screen::Pause,
+            selected: 0,
+            capturing: None,
+            skins,
+        }
+    }
+
+    pub fn key_down(&mut self, keycode: VirtualKeyCode, config: &mut Config) -> Option<MenuEvent> {
+        if self.capture(Input::Key(keycode), config) {
+            return (keycode != VirtualKeyCode::Escape).then_some(MenuEvent::BindingsChanged);
+        }
+        self.navigate(keycode, config)
+    }
+
+    /// Gamepads navigate the menu with the d-pad
+    pub fn button_down(&mut self, button: Button, config: &mut Config) -> Option<MenuEvent> {
+        if self.capture(Input::Button(button), config) {
+            return Some(MenuEvent::BindingsChanged);
+        }
+        let keycode = match button {
+            Button::DPadUp => VirtualKeyCode::Up,
+            Button::DPadDown => VirtualKeyCode::Down,
+            Button::DPadLeft => VirtualKeyCode::Left,
+            Button::DPadRight => VirtualKeyCode::Right,
+            Button::South => VirtualKeyCode::Return,
+            Button::East | Button::Start => VirtualKeyCode::Escape,
+            _ => return None,
+        };
+        self.navigate(keycode, config)
+    }
+
+    /// A stick was pushed all the way, which binds it if an action is
+    /// waiting for one
+    pub fn stick_pushed(&mut self, stick: Thumbstick, config: &mut Config) -> Option<MenuEvent> {
+        self.capturing
+            .is_some_and(|action| action.is_analog())
+            .then(|| self.capture(Input::Stick(stick), config))
+            .map(|_| MenuEvent::BindingsChanged)
+    }
+
+    /// Bind the input to the action waiting for one. Escape, an input the
+    /// config file has no name for, or a key or button for an action that
+    /// wants a stick, cancels instead. Returns false if no action was
+    /// waiting.
+    fn capture(&mut self, input: Input, config: &mut Config) -> bool {
+        let Some(action) = self.capturing.take() else {
+            return false;
+        };
+        let is_stick = matches!(input, Input::Stick(_));
+        if input != Input::Key(VirtualKeyCode::Escape)
+            && input.name().is_some()
+            && is_stick == action.is_analog()
+        {
+            config.bindings_mut(self.skier()).bind(action, input);
+        }
+        true
+    }
+
+    /// Skier whose bindings are being edited
+    fn skier(&self) -> usize {
+        match self.screen {
+            Screen::KeyBindings(skier) => skier,
+            _ => 0,
+        }
+    }
+
+    fn navigate(&mut self, keycode: VirtualKeyCode, config: &mut Config) -> Option<MenuEvent> {
+        let items = self.screen.items(&config.settings);
+        let item = items[self.selected];
+        match keycode {
+            VirtualKeyCode::Up => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(items.len() - 1);
+                None
+            }
+            VirtualKeyCode::Down => {
+                self.selected = (self.selected + 1) % items.len();
+                None
+            }
+            VirtualKeyCode::Escape => self.back(),
+            VirtualKeyCode::Left => self.adjust(item, &mut config.settings, false),
+            VirtualKeyCode::Right => self.adjust(item, &mut config.settings, true),
+            VirtualKeyCode::Return | VirtualKeyCode::Space => self.activate(item, config),
+            VirtualKeyCode::Back | VirtualKeyCode::Delete => match item {
+                Item::Bind(action) if !(self.skier() == 0 && action.is_required()) => {
+                    config.bindings_mut(self.skier()).clear(action);
+                    Some(MenuEvent::BindingsChanged)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn open(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.selected = 0;
+    }
+
+    fn back(&mut self) -> Option<MenuEvent> {
+        match self.screen {
+            Screen::Pause => Some(MenuEvent::Resume),
+            Screen::Options => {
+                self.open(Screen::Pause);
+                None
+            }
+            Screen::KeyBindings(_) => {
+                self.open(Screen::Options);
+                None
+            }
+        }
+    }
+
+    fn activate(&mut self, item: Item, config: &mut Config) -> Option<MenuEvent> {
+        match item {
+            Item::Resume => Some(MenuEvent::Resume),
+            Item::Restart => Some(MenuEvent::Restart),
+            Item::NewSlope => Some(MenuEvent::NewSlope),
+            Item::Quit => Some(MenuEvent::Quit),
+            Item::Options => {
+                self.open(Screen::Options);
+                None
+            }
+            Item::KeyBindings => {
+                self.open(Screen::KeyBindings(0));
+                None
+            }
+            Item::PlayerTwoKeys => {
+                self.open(Screen::KeyBindings(1));
+                None
+            }
+            Item::Bind(action) => {
+                self.capturing = Some(action);
+                None
+            }
+            Item::ResetBindings => {
+                *config.bindings_mut(self.skier()) = if self.skier() == 0 {
+                    Bindings::default()
+                } else {
+                    Bindings::player_two()
+                };
+                Some(MenuEvent::BindingsChanged)
+            }
+            Item::Back => self.back(),
+            Item::Players
+            | Item::Difficulty
+            | Item::Volume
+            | Item::MusicVolume
+            | Item::Fullscreen
+            | Item::Minimap
+            | Item::Skin => self.adjust(item, &mut config.settings, true),
+        }
+    }
+
+    /// Change the value of a setting. `up` increases it, otherwise decreases
+    fn adjust(&mut self, item: Item, settings: &mut Settings, up: bool) -> Option<MenuEvent> {
+        match item {
+            Item::Difficulty => {
+                settings.difficulty = if up {
+                    settings.difficulty.next()
+                } else {
+                    settings.difficulty.prev()
+                }
+            }
+            Item::Players => {
+                settings.players = if up {
+                    (settings.players + 1).min(Settings::MAX_PLAYERS)
+                } else {
+                    settings.players.saturating_sub(1).max(1)
+                }
+            }
+            Item::Volume => settings.volume = step_volume(settings.volume, up),
+            Item::MusicVolume => settings.music_volume = step_volume(settings.music_volume, up),
+            Item::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            Item::Minimap => settings.show_minimap = !settings.show_minimap,
+            Item::Skin => {
+                let current = self.skins.iter().position(|s| *s == settings.skin);
+                let next = match (current, up) {
+                    (Some(i), true) => (i + 1) % self.skins.len(),
+                    (Some(i), false) => i.checked_sub(1).unwrap_or(self.skins.len() - 1),
+                    (None, _) => 0,
+                };
+                settings.skin = self.skins[next];
+            }
+            _ => return None,
+        }
+        Some(MenuEvent::SettingsChanged)
+    }
+
+    pub fn draw(&self, ctx: &Context, canvas: &mut Canvas, config: &Config) {
+        let size = screen_size(ctx, canvas);
+        let (width, height) = (size.x, size.y);
+        let overlay = Rect::new(0., 0., width, height);
+        if let Ok(mesh) =
+            Mesh::new_rectangle(ctx, DrawMode::fill(), overlay, Color::new(0., 0., 0., 0.4))
+        {
+            canvas.draw(&mesh, DrawParam::default());
+        }
+
+        let mut lines = vec![self.screen.title().to_string(), String::new()];
+        let items = self.screen.items(&config.settings);
+        lines.extend(items.iter().enumerate().map(|(i, item)| {
+            let cursor = if i == self.selected { ">" } else { " " };
+            format!("{cursor} {}", item.label(config, self.skier()))
+        }));
+        if let Screen::KeyBindings(skier) = self.screen {
+            lines.push(String::new());
+            lines.push(match self.capturing {
+                Some(action) if action.is_analog() => {
+                    format!("Push a stick for {}", action.name())
+                }
+                Some(action) => format!("Press a key or button for {}", action.name()),
+                None => "Enter adds a key or button, Delete clears".to_string(),
+            });
+            for (input, actions) in config.bindings(skier).conflicts() {
+                let actions: Vec<_> = actions.iter().map(Action::name).collect();
+                lines.push(format!(
+                    "(!) {} is bound to {}",
+                    input.name().unwrap_or("?"),
+                    actions.join(", ")
+                ));
+            }
+            if config.settings.players > 1 {
+                for input in config.shared_inputs() {
+                    lines.push(format!(
+                        "(!) {} is bound for both players",
+                        input.name().unwrap_or("?")
+                    ));
+                }
+            }
+        }
+
+        let font_size = (height / 30.).clamp(12., 24.);
+        let mut text = Text::new(lines.join("\n"));
+        text.set_scale(PxScale::from(font_size));
+        let size = text.measure(ctx).map_or(Vec2::ZERO, Vec2::from);
+        let padding = font_size;
+        let panel = Rect::new(
+            (width - size.x) / 2. - padding,
+            (height - size.y) / 2. - padding,
+            size.x + padding * 2.,
+            size.y + padding * 2.,
+        );
+        if let Ok(fill) = Mesh::new_rectangle(ctx, DrawMode::fill(), panel, Color::WHITE) {
+            canvas.draw(&fill, DrawParam::default());
+        }
+        if let Ok(border) = Mesh::new_rectangle(ctx, DrawMode::stroke(2.), panel, Color::BLACK) {
+            canvas.draw(&border, DrawParam::default());
+        }
+        canvas.draw(
+            &text,
+            DrawParam::new()
+                .dest([panel.x + padding, panel.y + padding])
+                .color(Color::BLACK),
+        );
+    }
+}
+
+fn step_volume(volume: u8, up: bool) -> u8 {
+    if up {
+        (volume + 1).min(Settings::MAX_VOLUME)
+    } else {
+        volume.saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Difficulty;
+
+    fn press(menu: &mut Menu, config: &mut Config, keys: &[VirtualKeyCode]) -> Option<MenuEvent> {
+        keys.iter().fold(None, |_, key| menu.key_down(*key, config))
+    }
+
+    fn select(menu: &mut Menu, config: &Config, item: Item) {
+        let items = menu.screen.items(&config.settings);
+        menu.selected = items.iter().position(|i| *i == item).unwrap();
+    }
+
+    #[test]
+    fn navigates_screens_and_wraps_around() {
+        let mut config = Config::default();
+        let mut menu = Menu::pause(vec![Skin::Classic]);
+        assert_eq!(press(&mut menu, &mut config, &[VirtualKeyCode::Up]), None);
+        assert_eq!(
+            menu.screen.items(&config.settings)[menu.selected],
+            Item::Quit
+        );
+        press(&mut menu, &mut config, &[
+            VirtualKeyCode::Up,
+            VirtualKeyCode::Return,
+        ]);
+        assert_eq!(menu.screen, Screen::Options);
+        press(&mut menu, &mut config, &[VirtualKeyCode::Escape]);
+        assert_eq!(menu.screen, Screen::Pause);
+        assert_eq!(
+            press(&mut menu, &mut config, &[VirtualKeyCode::Escape]),
+            Some(MenuEvent::Resume)
+        );
+    }
+
+    #[test]
+    fn player_two_keys_only_with_two_players() {
+        let mut settings = Settings::default();
+        assert!(!Screen::Options
+            .items(&settings)
+            .contains(&Item::PlayerTwoKeys));
+        settings.players = 2;
+        assert!(Screen::Options
+            .items(&settings)
+            .contains(&Item::PlayerTwoKeys));
+    }
+
+    #[test]
+    fn marks_inputs_bound_for_both_players() {
+        let mut config = Config::default();
+        config.settings.players = 2;
+        let left = Input::Key(VirtualKeyCode::Left);
+        config.player_two.bind(Action::Flip, left);
+        assert!(Item::Bind(Action::Flip).label(&config, 1).ends_with("(!)"));
+        assert!(Item::Bind(Action::SteerLeft)
+            .label(&config, 0)
+            .ends_with("(!)"));
+        config.settings.players = 1;
+        assert!(!Item::Bind(Action::SteerLeft)
+            .label(&config, 0)
+            .ends_with("(!)"));
+    }
+
+    #[test]
+    fn adjusts_settings_within_limits() {
+        let mut config = Config::default();
+        let mut menu = Menu::pause(vec![Skin::Classic]);
+        menu.open(Screen::Options);
+        select(&mut menu, &config, Item::Difficulty);
+        for _ in 0..3 {
+            press(&mut menu, &mut config, &[VirtualKeyCode::Right]);
+        }
+        assert_eq!(config.settings.difficulty, Difficulty::Hard);
+        select(&mut menu, &config, Item::Volume);
+        for _ in 0..20 {
+            press(&mut menu, &mut config, &[VirtualKeyCode::Left]);
+        }
+        assert_eq!(config.settings.volume, 0);
+    }
+
+    #[test]
+    fn binds_and_clears_keys() {
+        let mut config = Config::default();
+        let mut menu = Menu::pause(vec![Skin::Classic]);
+        menu.open(Screen::KeyBindings(0));
+        assert_eq!(
+            press(&mut menu, &mut config, &[
+                VirtualKeyCode::Return,
+                VirtualKeyCode::A
+            ]),
+            Some(MenuEvent::BindingsChanged)
+        );
+        let a = Input::Key(VirtualKeyCode::A);
+        assert!(config.bindings(0).inputs(Action::SteerLeft).contains(&a));
+
+        // Escape cancels rather than being bound
+        press(&mut menu, &mut config, &[
+            VirtualKeyCode::Return,
+            VirtualKeyCode::Escape,
+        ]);
+        let escape = Input::Key(VirtualKeyCode::Escape);
+        assert!(!config
+            .bindings(0)
+            .inputs(Action::SteerLeft)
+            .contains(&escape));
+        assert_eq!(menu.screen, Screen::KeyBindings(0));
+
+        // so does a key that couldn't be saved
+        let kana = Input::Key(VirtualKeyCode::Kana);
+        assert_eq!(kana.name(), None);
+        press(&mut menu, &mut config, &[
+            VirtualKeyCode::Return,
+            VirtualKeyCode::Kana,
+        ]);
+        assert!(!config.bindings(0).inputs(Action::SteerLeft).contains(&kana));
+
+        press(&mut menu, &mut config, &[VirtualKeyCode::Delete]);
+        assert!(config.bindings(0).inputs(Action::SteerLeft).is_empty());
+    }
+
+    #[test]
+    fn binds_sticks_only_to_stick_steering() {
+        let mut config = Config::default();
+        let mut menu = Menu::pause(vec![Skin::Classic]);
+        menu.open(Screen::KeyBindings(1));
+        let right = Thumbstick::RightStick;
+        assert_eq!(menu.stick_pushed(right, &mut config), None);
+        select(&mut menu, &config, Item::Bind(Action::Steer));
+        press(&mut menu, &mut config, &[
+            VirtualKeyCode::Return,
+            VirtualKeyCode::A,
+        ]);
+        assert!(config.bindings(1).inputs(Action::Steer).is_empty());
+        press(&mut menu, &mut config, &[VirtualKeyCode::Return]);
+        assert_eq!(
+            menu.stick_pushed(right, &mut config),
+            Some(MenuEvent::BindingsChanged)
+        );
+        assert_eq!(config.bindings(1).inputs(Action::Steer), &[Input::Stick(
+            right
+        )]);
+    }
+
+    #[test]
+    fn keeps_a_way_to_pause_and_quit() {
+        let mut config = Config::default();
+        let mut menu = Menu::pause(vec![Skin::Classic]);
+        menu.open(Screen::KeyBindings(0));
+        for action in [Action::Pause, Action::Quit] {
+            select(&mut menu, &config, Item::Bind(action));
+            assert_eq!(
+                press(&mut menu, &mut config, &[VirtualKeyCode::Delete]),
+                None
+            );
+            assert!(!config.bindings(0).inputs(action).is_empty());
+        }
+    }
+}
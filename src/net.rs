@@ -0,0 +1,131 @@
+//! Races against other players over UDP. A server hands out the slope and
+//! relays where everyone is; each client simulates its own skier.
+
+pub mod broadcast;
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use ggez::glam::Vec2;
+
+    use super::client::Client;
+    use super::server::{Server, TIMEOUT_TICKS};
+    use crate::map::FINISH_Y;
+    use crate::player::{Player, Pose};
+    use crate::settings::Difficulty;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Join the server as `name`, ticking it until we're let in. Everything
+    /// after that runs in step: the clients send, then the server ticks,
+    /// which loopback delivers in time for.
+    fn join(server: &mut Server, name: &str) -> Client {
+        let addr = server.local_addr().unwrap();
+        let name = name.to_string();
+        let joining = thread::spawn(move || Client::connect(addr, &name, TIMEOUT));
+        while !joining.is_finished() {
+            server.tick().unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        joining.join().unwrap().unwrap()
+    }
+
+    /// Where a skier going down at `speed` pixels a tick is after `ticks`
+    fn down(x: f32, speed: f32, ticks: u32) -> Vec2 {
+        Vec2::new(x, Player::POSITION[1] + speed * ticks as f32)
+    }
+
+    #[test]
+    fn race_on_loopback() {
+        let mut server = Server::bind("127.0.0.1:0", 1234, Difficulty::Hard).unwrap();
+        let mut alice = join(&mut server, "alice");
+        let mut bob = join(&mut server, "bob");
+        assert_eq!(alice.seed(), 1234);
+        assert_eq!(bob.difficulty(), Difficulty::Hard);
+        assert_ne!(alice.id(), bob.id());
+
+        for tick in 0..2000 {
+            let (a, b) = (down(0., 5.5, tick), down(100., 5.8, tick));
+            if a.y >= FINISH_Y as f32 {
+                alice.finish();
+            }
+            if b.y >= FINISH_Y as f32 {
+                bob.finish();
+            }
+            alice.update(a, Pose::Down).unwrap();
+            bob.update(b, Pose::Left30).unwrap();
+            server.tick().unwrap();
+        }
+
+        // each sees the other, not themselves
+        let seen = alice.skiers();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].id, bob.id());
+        assert_eq!(seen[0].x, 100.);
+        assert!(seen[0].y > 0.);
+        assert_eq!(bob.skiers()[0].id, alice.id());
+
+        let names: Vec<_> = server.results().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["bob", "alice"]);
+        assert_eq!(alice.results(), server.results());
+
+        // a restart starts everyone again
+        bob.restart().unwrap();
+        for _ in 0..2 {
+            alice.update(down(0., 0., 0), Pose::Down).unwrap();
+            bob.update(down(100., 0., 0), Pose::Down).unwrap();
+            server.tick().unwrap();
+        }
+        assert!(alice.take_restart() && bob.take_restart());
+        assert!(server.results().is_empty() && alice.results().is_empty());
+    }
+
+    #[test]
+    fn quiet_clients_time_out_and_can_rejoin() {
+        let mut server = Server::bind("127.0.0.1:0", 1, Difficulty::Easy).unwrap();
+        let mut alice = join(&mut server, "alice");
+        let bob = join(&mut server, "bob");
+        // alice is paused, bob has gone quiet
+        for _ in 0..=TIMEOUT_TICKS {
+            alice.keep_alive(down(0., 0., 0), Pose::Down).unwrap();
+            server.tick().unwrap();
+        }
+        assert_eq!(server.players(), ["alice"]);
+
+        let old_id = bob.id();
+        drop(bob);
+        let bob = join(&mut server, "bob");
+        assert_ne!(bob.id(), old_id);
+        assert_eq!(server.players(), ["alice", "bob"]);
+    }
+
+    #[test]
+    fn finishing_early_doesnt_count() {
+        let mut server = Server::bind("127.0.0.1:0", 1, Difficulty::Easy).unwrap();
+        let mut alice = join(&mut server, "alice");
+        // says so without getting there
+        alice.finish();
+        for tick in 0..10 {
+            alice.update(down(0., 3., tick), Pose::Down).unwrap();
+            server.tick().unwrap();
+        }
+        assert!(server.results().is_empty());
+
+        // gets there faster than anyone could
+        for _ in 0..10 {
+            alice
+                .update(down(0., 0., 0) + Vec2::Y * FINISH_Y as f32, Pose::Down)
+                .unwrap();
+            server.tick().unwrap();
+        }
+        assert!(server.results().is_empty());
+        assert!(alice.results().is_empty());
+    }
+}
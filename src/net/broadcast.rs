@@ -0,0 +1,188 @@
+//! Streams the live game to read-only spectators over TCP. Every message of
+//! the usual protocol goes out with a two byte length in front: a `Welcome`
+//! with the slope whenever a run starts, then a `Scene` every tick.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use anyhow::{bail, Context as _, Result};
+
+use super::protocol::{Message, SpectatedSkier};
+use crate::settings::Difficulty;
+
+pub const DEFAULT_PORT: u16 = 7879;
+
+/// The game's side, sending to every spectator that connects
+#[derive(Debug)]
+pub struct Broadcast {
+    listener: TcpListener,
+    spectators: Vec<TcpStream>,
+    welcome: Message,
+    tick: u32,
+}
+
+impl Broadcast {
+    pub fn listen(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr).context("listening for spectators")?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            spectators: vec![],
+            welcome: Message::Welcome {
+                id: 0,
+                seed: 0,
+                difficulty: Difficulty::Normal,
+                race: 0,
+            },
+            tick: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<std::net::SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// A new run started on the slope with `seed`
+    pub fn start(&mut self, seed: u64, difficulty: Difficulty) {
+        self.tick = 0;
+        self.welcome = Message::Welcome {
+            id: 0,
+            seed,
+            difficulty,
+            race: 0,
+        };
+        let welcome = self.welcome.clone();
+        self.send(&welcome);
+    }
+
+    /// Let in anyone waiting and send out how the skiers are doing this tick
+    pub fn update(&mut self, skiers: Vec<SpectatedSkier>) {
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, addr)) => {
+                    eprintln!("spectator connected from {addr}");
+                    // written to while the game is running, so never wait
+                    let welcome = stream
+                        .set_nonblocking(true)
+                        .and_then(|_| stream.set_nodelay(true))
+                        .and_then(|_| write_frame(&mut stream, &self.welcome));
+                    if welcome.is_ok() {
+                        self.spectators.push(stream);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("error accepting spectator: {e}");
+                    break;
+                }
+            }
+        }
+        self.tick += 1;
+        self.send(&Message::Scene {
+            tick: self.tick,
+            skiers,
+        });
+    }
+
+    /// Send to everyone, dropping spectators that went away or can't keep up
+    fn send(&mut self, message: &Message) {
+        self.spectators
+            .retain_mut(|stream| write_frame(stream, message).is_ok());
+    }
+}
+
+fn write_frame(stream: &mut TcpStream, message: &Message) -> std::io::Result<()> {
+    let data = message.encode();
+    let mut frame = (data.len() as u16).to_be_bytes().to_vec();
+    frame.extend(data);
+    stream.write_all(&frame)
+}
+
+/// The spectator's side, reading what the game sends
+#[derive(Debug)]
+pub struct Watch {
+    stream: TcpStream,
+    /// Received bytes not yet making up a whole message
+    buf: Vec<u8>,
+}
+
+impl Watch {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr).context("connecting to the game")?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            buf: vec![],
+        })
+    }
+
+    /// Messages received since the last call
+    pub fn poll(&mut self) -> Result<Vec<Message>> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => bail!("the game has ended"),
+                Ok(len) => self.buf.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e).context("reading from the game"),
+            }
+        }
+        let mut messages = vec![];
+        while self.buf.len() >= 2 {
+            let len = u16::from_be_bytes([self.buf[0], self.buf[1]]) as usize;
+            if self.buf.len() < 2 + len {
+                break;
+            }
+            messages.push(Message::decode(&self.buf[2..2 + len])?);
+            self.buf.drain(..2 + len);
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::net::protocol::RemoteSkier;
+    use crate::player::Pose;
+
+    #[test]
+    fn spectator_sees_the_run() {
+        let mut broadcast = Broadcast::listen("127.0.0.1:0").unwrap();
+        broadcast.start(99, Difficulty::Easy);
+        let mut watch = Watch::connect(broadcast.local_addr().unwrap()).unwrap();
+        let skier = SpectatedSkier {
+            skier: RemoteSkier {
+                id: 0,
+                x: 1.,
+                y: 2.,
+                pose: Pose::Jump,
+            },
+            run: None,
+            yeti: None,
+        };
+
+        let mut messages = vec![];
+        for _ in 0..100 {
+            broadcast.update(vec![skier]);
+            thread::sleep(Duration::from_millis(2));
+            messages.extend(watch.poll().unwrap());
+            if messages.len() >= 2 {
+                break;
+            }
+        }
+        assert_eq!(messages[0], Message::Welcome {
+            id: 0,
+            seed: 99,
+            difficulty: Difficulty::Easy,
+            race: 0,
+        });
+        assert!(matches!(
+            &messages[1],
+            Message::Scene { skiers, .. } if skiers == &[skier]
+        ));
+    }
+}
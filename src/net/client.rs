@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use ggez::glam::Vec2;
+
+use super::protocol::{Message, RemoteSkier, Standing, MAX_SIZE};
+use crate::map::wrap_x;
+use crate::player::Pose;
+use crate::settings::Difficulty;
+
+/// Other skiers are shown this many ticks in the past, so there's usually a
+/// snapshot either side to smooth between
+pub const INTERPOLATION_DELAY: f32 = 6.;
+/// Snapshots of each skier kept around
+const HISTORY_LEN: usize = 60;
+const JOIN_RETRY: Duration = Duration::from_millis(250);
+
+/// Connection to a race server
+#[derive(Debug)]
+pub struct Client {
+    socket: UdpSocket,
+    id: u8,
+    seed: u64,
+    difficulty: Difficulty,
+    /// Ticks simulated locally
+    tick: u32,
+    /// Best guess of the server's current tick
+    server_tick: f32,
+    latest_snapshot: u32,
+    /// Recent snapshots of each of the other skiers, oldest first
+    skiers: BTreeMap<u8, VecDeque<(u32, RemoteSkier)>>,
+    /// Crossed the finish line, but the server may not know yet
+    finished: bool,
+    results: Vec<Standing>,
+    /// Race the server is running
+    race: u32,
+    /// The server started a new race we haven't restarted for yet
+    restarting: bool,
+}
+
+impl Client {
+    /// Join the race on the server at `addr`, giving up after `timeout`
+    pub fn connect(addr: impl ToSocketAddrs, name: &str, timeout: Duration) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("no address to connect to"))?;
+        let local: std::net::SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_read_timeout(Some(JOIN_RETRY))?;
+
+        let join = Message::Join { name: name.into() }.encode();
+        let start = Instant::now();
+        let mut buf = [0; MAX_SIZE];
+        let (id, seed, difficulty, race) = 'join: loop {
+            if start.elapsed() > timeout {
+                bail!("no answer from {addr}");
+            }
+            socket.send(&join)?;
+            // snapshots may arrive ahead of the welcome
+            while let Ok(len) = socket.recv(&mut buf) {
+                let message = match Message::decode(&buf[..len]) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        eprintln!("ignoring message from server: {e:#}");
+                        continue;
+                    }
+                };
+                match message {
+                    Message::Welcome {
+                        id,
+                        seed,
+                        difficulty,
+                        race,
+                    } => break 'join (id, seed, difficulty, race),
+                    Message::Rejected { reason } => bail!("server rejected us: {reason}"),
+                    _ => {}
+                }
+            }
+        };
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            id,
+            seed,
+            difficulty,
+            tick: 0,
+            server_tick: 0.,
+            latest_snapshot: 0,
+            skiers: BTreeMap::new(),
+            finished: false,
+            results: vec![],
+            race,
+            restarting: false,
+        })
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Everyone who has finished, fastest first
+    pub fn results(&self) -> &[Standing] {
+        &self.results
+    }
+
+    /// Tell the server where our skier is this tick and take in whatever it
+    /// has sent
+    pub fn update(&mut self, position: Vec2, pose: Pose) -> Result<()> {
+        self.tick += 1;
+        self.keep_alive(position, pose)
+    }
+
+    /// Like `update`, for when the skier is standing still, e.g. while the
+    /// game is paused, so the server doesn't time us out
+    pub fn keep_alive(&mut self, position: Vec2, pose: Pose) -> Result<()> {
+        self.server_tick += 1.;
+        self.send(&Message::State {
+            tick: self.tick,
+            x: position.x,
+            y: position.y,
+            pose,
+        })?;
+        if self.finished && !self.results.iter().any(|s| s.id == self.id) {
+            self.send(&Message::Finished)?;
+        }
+
+        let mut buf = [0; MAX_SIZE];
+        loop {
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e).context("receiving"),
+            };
+            let message = match Message::decode(&buf[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("ignoring message from server: {e:#}");
+                    continue;
+                }
+            };
+            match message {
+                Message::Snapshot { tick, skiers } => self.snapshot(tick, skiers),
+                Message::Results(results) => self.results = results,
+                Message::Start { race } if race != self.race => {
+                    self.race = race;
+                    self.finished = false;
+                    self.results.clear();
+                    self.restarting = true;
+                }
+                Message::Rejected { reason } => bail!("server rejected us: {reason}"),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot(&mut self, tick: u32, skiers: Vec<RemoteSkier>) {
+        // older snapshots than we have arrived out of order
+        if tick <= self.latest_snapshot {
+            return;
+        }
+        self.latest_snapshot = tick;
+        self.server_tick = tick as f32;
+        for skier in skiers.into_iter().filter(|s| s.id != self.id) {
+            let history = self.skiers.entry(skier.id).or_default();
+            history.push_back((tick, skier));
+            if history.len() > HISTORY_LEN {
+                history.pop_front();
+            }
+        }
+        // skiers missing from recent snapshots have left
+        self.skiers.retain(|_, history| {
+            history
+                .back()
+                .is_some_and(|(t, _)| tick - t < HISTORY_LEN as u32)
+        });
+    }
+
+    /// Let the server know we crossed the finish line, along with the
+    /// position that shows it on the next update
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Ask the server to start everyone again
+    pub fn restart(&self) -> Result<()> {
+        self.send(&Message::Restart)
+    }
+
+    /// Whether the server started a new race since last asked, which our
+    /// skier should start again for
+    pub fn take_restart(&mut self) -> bool {
+        std::mem::take(&mut self.restarting)
+    }
+
+    /// The other skiers, somewhere between the snapshots either side of
+    /// `INTERPOLATION_DELAY` ticks ago
+    pub fn skiers(&self) -> Vec<RemoteSkier> {
+        let tick = self.server_tick - INTERPOLATION_DELAY;
+        self.skiers
+            .values()
+            .filter_map(|history| interpolate(history, tick))
+            .collect()
+    }
+
+    fn send(&self, message: &Message) -> Result<()> {
+        match self.socket.send(&message.encode()) {
+            Err(e) if e.kind() != ErrorKind::WouldBlock => Err(e).context("sending to server"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // the server times us out anyway if this gets lost
+        let _ = self.send(&Message::Leave);
+    }
+}
+
+/// Where the skier was on `tick`, between the snapshots either side of it
+fn interpolate(history: &VecDeque<(u32, RemoteSkier)>, tick: f32) -> Option<RemoteSkier> {
+    let after = history.iter().position(|(t, _)| *t as f32 >= tick);
+    let (before, after) = match after {
+        Some(0) => return history.front().map(|(_, s)| *s),
+        Some(i) => (history[i - 1], history[i]),
+        // nothing that new yet, so don't guess
+        None => return history.back().map(|(_, s)| *s),
+    };
+    let (t0, a) = before;
+    let (t1, b) = after;
+    let amount = (tick - t0 as f32) / (t1 - t0) as f32;
+    // the shortest way round if the skier wrapped around the map
+    let bx = wrap_x(b.x, a.x);
+    Some(RemoteSkier {
+        id: a.id,
+        x: a.x + (bx - a.x) * amount,
+        y: a.y + (b.y - a.y) * amount,
+        pose: if amount < 0.5 { a.pose } else { b.pose },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skier(x: f32, y: f32) -> RemoteSkier {
+        RemoteSkier {
+            id: 2,
+            x,
+            y,
+            pose: Pose::Down,
+        }
+    }
+
+    #[test]
+    fn interpolates_between_snapshots() {
+        let history = VecDeque::from([(10, skier(0., 0.)), (14, skier(40., 100.))]);
+        assert_eq!(interpolate(&history, 11.), Some(skier(10., 25.)));
+        assert_eq!(interpolate(&history, 5.), Some(skier(0., 0.)));
+        assert_eq!(interpolate(&history, 20.), Some(skier(40., 100.)));
+        assert_eq!(interpolate(&VecDeque::new(), 1.), None);
+    }
+}
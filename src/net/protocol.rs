@@ -0,0 +1,474 @@
+//! Messages between the race server and its clients. Each datagram holds one
+//! message: `MAGIC`, `VERSION` and a tag byte, then the message's fields in
+//! big-endian order. Strings are a length byte followed by UTF-8.
+
+use anyhow::{bail, Context as _, Result};
+use ggez::glam::Vec2;
+
+use crate::player::Pose;
+use crate::score::Score;
+use crate::settings::Difficulty;
+use crate::yeti::YetiSighting;
+
+pub const MAGIC: [u8; 2] = *b"SF";
+/// Bumped whenever the layout of a message changes
+pub const VERSION: u8 = 2;
+/// Big enough for any message we send
+pub const MAX_SIZE: usize = 1200;
+/// Names are cut down to this many bytes
+pub const MAX_NAME_LEN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Client asks to join the race
+    Join { name: String },
+    /// Server lets the client in, with the slope to race on and the race
+    /// under way
+    Welcome {
+        id: u8,
+        seed: u64,
+        difficulty: Difficulty,
+        race: u32,
+    },
+    /// Server turns the client away
+    Rejected { reason: String },
+    /// Where the client's skier was on the client's `tick`
+    State {
+        tick: u32,
+        x: f32,
+        y: f32,
+        pose: Pose,
+    },
+    /// Where every skier was on the server's `tick`
+    Snapshot { tick: u32, skiers: Vec<RemoteSkier> },
+    /// Client crossed the finish line
+    Finished,
+    /// Everyone who has finished, fastest first
+    Results(Vec<Standing>),
+    /// Client is going away
+    Leave,
+    /// Client wants everyone to start again
+    Restart,
+    /// Server started race number `race`, and everyone's clock with it
+    Start { race: u32 },
+    /// Every skier on the game's `tick`, as spectators see them
+    Scene {
+        tick: u32,
+        skiers: Vec<SpectatedSkier>,
+    },
+}
+
+/// A skier as seen by the other players
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemoteSkier {
+    pub id: u8,
+    pub x: f32,
+    pub y: f32,
+    pub pose: Pose,
+}
+
+/// A skier as spectators see them. How the run is going is only known for
+/// skiers playing in the broadcasting game, not others racing it online.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectatedSkier {
+    pub skier: RemoteSkier,
+    pub run: Option<RunStatus>,
+    pub yeti: Option<YetiSighting>,
+}
+
+/// What a skier's HUD and score show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunStatus {
+    /// Seconds into the run
+    pub time: f32,
+    pub distance: f32,
+    /// Pixels a second
+    pub speed: f32,
+    pub score: Score,
+}
+
+/// A skier who made it to the finish line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub id: u8,
+    pub name: String,
+    /// Server ticks from the start of the race to finishing
+    pub ticks: u32,
+}
+
+impl Message {
+    fn tag(&self) -> u8 {
+        match self {
+            Message::Join { .. } => 0,
+            Message::Welcome { .. } => 1,
+            Message::Rejected { .. } => 2,
+            Message::State { .. } => 3,
+            Message::Snapshot { .. } => 4,
+            Message::Finished => 5,
+            Message::Results(_) => 6,
+            Message::Leave => 7,
+            Message::Restart => 8,
+            Message::Start { .. } => 9,
+            Message::Scene { .. } => 10,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::with_capacity(64));
+        w.bytes(&MAGIC);
+        w.u8(VERSION);
+        w.u8(self.tag());
+        match self {
+            Message::Join { name } => w.string(name),
+            Message::Welcome {
+                id,
+                seed,
+                difficulty,
+                race,
+            } => {
+                w.u8(*id);
+                w.u64(*seed);
+                w.u8(index_of(&Difficulty::ALL, difficulty));
+                w.u32(*race);
+            }
+            Message::Rejected { reason } => w.string(reason),
+            Message::State { tick, x, y, pose } => {
+                w.u32(*tick);
+                w.f32(*x);
+                w.f32(*y);
+                w.u8(index_of(&Pose::ALL, pose));
+            }
+            Message::Snapshot { tick, skiers } => {
+                w.u32(*tick);
+                w.u8(skiers.len() as u8);
+                for skier in skiers {
+                    w.skier(skier);
+                }
+            }
+            Message::Results(standings) => {
+                w.u8(standings.len() as u8);
+                for standing in standings {
+                    w.u8(standing.id);
+                    w.string(&standing.name);
+                    w.u32(standing.ticks);
+                }
+            }
+            Message::Start { race } => w.u32(*race),
+            Message::Scene { tick, skiers } => {
+                w.u32(*tick);
+                w.u8(skiers.len() as u8);
+                for spectated in skiers {
+                    w.skier(&spectated.skier);
+                    w.u8(spectated.run.is_some() as u8);
+                    if let Some(run) = &spectated.run {
+                        w.f32(run.time);
+                        w.f32(run.distance);
+                        w.f32(run.speed);
+                        w.u32(run.score.style as u32);
+                        w.u32(run.score.gates_passed);
+                        w.u32(run.score.gates_missed);
+                    }
+                    w.u8(spectated.yeti.is_some() as u8);
+                    if let Some(yeti) = &spectated.yeti {
+                        w.f32(yeti.position.x);
+                        w.f32(yeti.position.y);
+                        w.u8(yeti.eating as u8);
+                        w.u32(yeti.tick);
+                    }
+                }
+            }
+            Message::Finished | Message::Leave | Message::Restart => {}
+        }
+        w.0
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut r = Reader(data);
+        if r.bytes(2)? != MAGIC {
+            bail!("not a skifree message");
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            bail!("protocol version {version}, expected {VERSION}");
+        }
+        let message = match r.u8()? {
+            0 => Message::Join { name: r.string()? },
+            1 => Message::Welcome {
+                id: r.u8()?,
+                seed: r.u64()?,
+                difficulty: r.pick(&Difficulty::ALL)?,
+                race: r.u32()?,
+            },
+            2 => Message::Rejected {
+                reason: r.string()?,
+            },
+            3 => Message::State {
+                tick: r.u32()?,
+                x: r.f32()?,
+                y: r.f32()?,
+                pose: r.pick(&Pose::ALL)?,
+            },
+            4 => {
+                let tick = r.u32()?;
+                let skiers = (0..r.u8()?).map(|_| r.skier()).collect::<Result<_>>()?;
+                Message::Snapshot { tick, skiers }
+            }
+            5 => Message::Finished,
+            6 => Message::Results(
+                (0..r.u8()?)
+                    .map(|_| {
+                        Ok(Standing {
+                            id: r.u8()?,
+                            name: r.string()?,
+                            ticks: r.u32()?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            7 => Message::Leave,
+            8 => Message::Restart,
+            9 => Message::Start { race: r.u32()? },
+            10 => {
+                let tick = r.u32()?;
+                let skiers = (0..r.u8()?)
+                    .map(|_| {
+                        let skier = r.skier()?;
+                        let run = match r.u8()? {
+                            0 => None,
+                            _ => Some(RunStatus {
+                                time: r.f32()?,
+                                distance: r.f32()?,
+                                speed: r.f32()?,
+                                score: Score {
+                                    style: r.u32()? as i32,
+                                    gates_passed: r.u32()?,
+                                    gates_missed: r.u32()?,
+                                },
+                            }),
+                        };
+                        let yeti = match r.u8()? {
+                            0 => None,
+                            _ => Some(YetiSighting {
+                                position: Vec2::new(r.f32()?, r.f32()?),
+                                eating: r.u8()? != 0,
+                                tick: r.u32()?,
+                            }),
+                        };
+                        Ok(SpectatedSkier { skier, run, yeti })
+                    })
+                    .collect::<Result<_>>()?;
+                Message::Scene { tick, skiers }
+            }
+            tag => bail!("unknown message type {tag}"),
+        };
+        if !r.0.is_empty() {
+            bail!("{} bytes left over", r.0.len());
+        }
+        Ok(message)
+    }
+}
+
+fn index_of<T: PartialEq>(all: &[T], value: &T) -> u8 {
+    all.iter().position(|v| v == value).unwrap_or_default() as u8
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.0.push(n);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes(&n.to_be_bytes());
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.bytes(&n.to_be_bytes());
+    }
+
+    fn f32(&mut self, n: f32) {
+        self.bytes(&n.to_be_bytes());
+    }
+
+    fn skier(&mut self, skier: &RemoteSkier) {
+        self.u8(skier.id);
+        self.f32(skier.x);
+        self.f32(skier.y);
+        self.u8(index_of(&Pose::ALL, &skier.pose));
+    }
+
+    fn string(&mut self, s: &str) {
+        // cut at a character boundary
+        let mut len = s.len().min(u8::MAX as usize);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.u8(len as u8);
+        self.bytes(&s.as_bytes()[..len]);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("message too short");
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into()?)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_be_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).context("invalid string")
+    }
+
+    fn skier(&mut self) -> Result<RemoteSkier> {
+        Ok(RemoteSkier {
+            id: self.u8()?,
+            x: self.f32()?,
+            y: self.f32()?,
+            pose: self.pick(&Pose::ALL)?,
+        })
+    }
+
+    /// One of `all`, by its index
+    fn pick<T: Copy>(&mut self, all: &[T]) -> Result<T> {
+        let index = self.u8()?;
+        all.get(index as usize)
+            .copied()
+            .with_context(|| format!("invalid value {index}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_round_trip_messages() {
+        let messages = [
+            Message::Join {
+                name: "trevor".into(),
+            },
+            Message::Welcome {
+                id: 3,
+                seed: 0xdead_beef_cafe,
+                difficulty: Difficulty::Hard,
+                race: 2,
+            },
+            Message::State {
+                tick: 600,
+                x: -12.5,
+                y: 4000.25,
+                pose: Pose::Flip3,
+            },
+            Message::Snapshot {
+                tick: 42,
+                skiers: vec![RemoteSkier {
+                    id: 1,
+                    x: 240.,
+                    y: 200.,
+                    pose: Pose::Down,
+                }],
+            },
+            Message::Results(vec![Standing {
+                id: 2,
+                name: "ümlaut".into(),
+                ticks: 3600,
+            }]),
+            Message::Finished,
+            Message::Leave,
+            Message::Restart,
+            Message::Start { race: 7 },
+            Message::Scene {
+                tick: 9,
+                skiers: vec![
+                    SpectatedSkier {
+                        skier: RemoteSkier {
+                            id: 0,
+                            x: 1.,
+                            y: 2.,
+                            pose: Pose::Left45,
+                        },
+                        run: Some(RunStatus {
+                            time: 12.5,
+                            distance: 300.,
+                            speed: 180.,
+                            score: Score {
+                                style: -5,
+                                gates_passed: 3,
+                                gates_missed: 1,
+                            },
+                        }),
+                        yeti: Some(YetiSighting {
+                            position: Vec2::new(-40., 100.),
+                            eating: true,
+                            tick: 17,
+                        }),
+                    },
+                    SpectatedSkier {
+                        skier: RemoteSkier {
+                            id: 1,
+                            x: 3.,
+                            y: 4.,
+                            pose: Pose::Down,
+                        },
+                        run: None,
+                        yeti: None,
+                    },
+                ],
+            },
+        ];
+        for message in messages {
+            let data = message.encode();
+            assert!(data.len() <= MAX_SIZE);
+            assert_eq!(Message::decode(&data).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_messages() {
+        let mut data = Message::Leave.encode();
+        data[2] = VERSION + 1;
+        let error = Message::decode(&data).unwrap_err();
+        assert!(error.to_string().contains("version"));
+
+        let data = Message::Welcome {
+            id: 1,
+            seed: 1,
+            difficulty: Difficulty::Easy,
+            race: 0,
+        }
+        .encode();
+        assert!(Message::decode(&data[..data.len() - 1]).is_err());
+        assert!(Message::decode(b"hello").is_err());
+    }
+}
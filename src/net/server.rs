@@ -0,0 +1,273 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result};
+
+use super::protocol::{Message, RemoteSkier, Standing, MAX_NAME_LEN, MAX_SIZE};
+use crate::headless::TICKS_PER_SECOND;
+use crate::map::FINISH_Y;
+use crate::player::Player;
+use crate::settings::Difficulty;
+
+/// Most skiers in one race
+pub const MAX_PLAYERS: usize = 8;
+/// Clients that haven't sent anything for this long are dropped
+pub const TIMEOUT_TICKS: u32 = 5 * TICKS_PER_SECOND;
+/// Quickest anyone can get from the top to the finish line
+const FASTEST_RUN: u32 = ((FINISH_Y as f32 - Player::POSITION[1]) / Player::MAX_SPEED) as u32;
+
+/// A client that has joined the race
+#[derive(Debug)]
+struct Peer {
+    addr: SocketAddr,
+    id: u8,
+    name: String,
+    last_heard: u32,
+    /// Client tick of the latest state, to ignore ones arriving late
+    state_tick: u32,
+    skier: Option<RemoteSkier>,
+    finished: bool,
+}
+
+/// Hands out the slope, relays where everyone is and keeps the times. Everyone
+/// is timed on the server's clock from the start of the race, and a finish
+/// only counts once the skier's own positions are past the line, so a client
+/// can't claim a faster one.
+#[derive(Debug)]
+pub struct Server {
+    socket: UdpSocket,
+    seed: u64,
+    difficulty: Difficulty,
+    tick: u32,
+    /// Races started so far, the first being 0
+    race: u32,
+    /// Tick the race started on
+    started: u32,
+    peers: Vec<Peer>,
+    next_id: u8,
+    results: Vec<Standing>,
+}
+
+impl Server {
+    pub fn bind(addr: impl ToSocketAddrs, seed: u64, difficulty: Difficulty) -> Result<Self> {
+        let socket = UdpSocket::bind(addr).context("binding server socket")?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            seed,
+            difficulty,
+            tick: 0,
+            race: 0,
+            started: 0,
+            peers: vec![],
+            next_id: 1,
+            results: vec![],
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Everyone who has finished so far, fastest first
+    pub fn results(&self) -> &[Standing] {
+        &self.results
+    }
+
+    /// Names of everyone in the race
+    pub fn players(&self) -> Vec<&str> {
+        self.peers.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Tick at `TICKS_PER_SECOND` forever
+    pub fn run(mut self) -> Result<!> {
+        let tick_time = Duration::from_secs(1) / TICKS_PER_SECOND;
+        loop {
+            let start = Instant::now();
+            self.tick()?;
+            std::thread::sleep(tick_time.saturating_sub(start.elapsed()));
+        }
+    }
+
+    /// Handle everything received since the last tick and send everyone a
+    /// snapshot
+    pub fn tick(&mut self) -> Result<()> {
+        self.tick += 1;
+        let mut buf = [0; MAX_SIZE];
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // e.g. a client's port being closed on some platforms
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e).context("receiving"),
+            };
+            match Message::decode(&buf[..len]) {
+                Ok(message) => self.handle(addr, message),
+                Err(e) => self.send(addr, &Message::Rejected {
+                    reason: format!("{e:#}"),
+                }),
+            }
+        }
+
+        let tick = self.tick;
+        self.peers.retain(|p| {
+            let alive = tick - p.last_heard < TIMEOUT_TICKS;
+            if !alive {
+                eprintln!("{} timed out", p.name);
+            }
+            alive
+        });
+
+        let snapshot = Message::Snapshot {
+            tick,
+            skiers: self.peers.iter().filter_map(|p| p.skier).collect(),
+        };
+        self.broadcast(&snapshot);
+        // now and again, in case the last ones got lost
+        if tick % TICKS_PER_SECOND == 0 {
+            self.broadcast(&Message::Start { race: self.race });
+            if !self.results.is_empty() {
+                self.broadcast(&Message::Results(self.results.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a new race, with everyone back at the top and the clock at 0
+    fn restart(&mut self) {
+        self.race += 1;
+        self.started = self.tick;
+        self.results.clear();
+        for peer in &mut self.peers {
+            peer.skier = None;
+            peer.finished = false;
+        }
+        eprintln!("race {} started", self.race);
+        self.broadcast(&Message::Start { race: self.race });
+    }
+
+    fn handle(&mut self, addr: SocketAddr, message: Message) {
+        let tick = self.tick;
+        let Some(index) = self.peers.iter().position(|p| p.addr == addr) else {
+            if let Message::Join { name } = message {
+                self.join(addr, name);
+            }
+            return;
+        };
+        let peer = &mut self.peers[index];
+        peer.last_heard = tick;
+        match message {
+            // the welcome got lost
+            Message::Join { .. } => {
+                let welcome = self.welcome(self.peers[index].id);
+                self.send(addr, &welcome);
+            }
+            Message::State {
+                tick: state_tick,
+                x,
+                y,
+                pose,
+            } => {
+                if peer.skier.is_none() || state_tick > peer.state_tick {
+                    peer.state_tick = state_tick;
+                    peer.skier = Some(RemoteSkier {
+                        id: peer.id,
+                        x,
+                        y,
+                        pose,
+                    });
+                }
+            }
+            Message::Finished => {
+                if !peer.finished {
+                    let ticks = tick - self.started;
+                    // where the server last saw them, not where they say
+                    // they are. The client asks again with its next state.
+                    let past_line = peer.skier.is_some_and(|s| s.y >= FINISH_Y as f32);
+                    if !past_line || ticks < FASTEST_RUN {
+                        return;
+                    }
+                    peer.finished = true;
+                    self.results.push(Standing {
+                        id: peer.id,
+                        name: peer.name.clone(),
+                        ticks,
+                    });
+                    self.results.sort_by_key(|s| s.ticks);
+                    eprintln!("{} finished in {ticks} ticks", peer.name);
+                }
+                // also tells the client its finish arrived
+                self.broadcast(&Message::Results(self.results.clone()));
+            }
+            Message::Leave => {
+                eprintln!("{} left", peer.name);
+                self.peers.remove(index);
+            }
+            Message::Restart => {
+                eprintln!("{} asked for a restart", peer.name);
+                self.restart();
+            }
+            Message::Welcome { .. }
+            | Message::Rejected { .. }
+            | Message::Snapshot { .. }
+            | Message::Results(_)
+            | Message::Start { .. }
+            | Message::Scene { .. } => {}
+        }
+    }
+
+    fn join(&mut self, addr: SocketAddr, mut name: String) {
+        if self.peers.len() >= MAX_PLAYERS || self.next_id == u8::MAX {
+            return self.send(addr, &Message::Rejected {
+                reason: "the race is full".into(),
+            });
+        }
+        let mut len = name.len().min(MAX_NAME_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        name.truncate(len);
+        let id = self.next_id;
+        self.next_id += 1;
+        eprintln!("{name} joined from {addr} as player {id}");
+        self.peers.push(Peer {
+            addr,
+            id,
+            name,
+            last_heard: self.tick,
+            state_tick: 0,
+            skier: None,
+            finished: false,
+        });
+        self.send(addr, &self.welcome(id))
+    }
+
+    fn welcome(&self, id: u8) -> Message {
+        Message::Welcome {
+            id,
+            seed: self.seed,
+            difficulty: self.difficulty,
+            race: self.race,
+        }
+    }
+
+    /// Send to one client. A failed send is only logged, as one bad peer
+    /// shouldn't stop the race for everyone else; it times out if it's gone.
+    fn send(&self, addr: SocketAddr, message: &Message) {
+        match self.socket.send_to(&message.encode(), addr) {
+            // nothing to do about a full buffer but drop the datagram
+            Err(e) if e.kind() != ErrorKind::WouldBlock => {
+                eprintln!("error sending to {addr}: {e}")
+            }
+            _ => {}
+        }
+    }
+
+    fn broadcast(&self, message: &Message) {
+        for peer in &self.peers {
+            self.send(peer.addr, message);
+        }
+    }
+}
@@ -1,28 +1,76 @@
 use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_6, PI};
 use std::rc::Rc;
 
-use ggez::graphics::{Canvas, DrawParam, Image, Rect};
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, DrawParam, Rect};
 use ggez::Context;
+use serde::{Deserialize, Serialize};
 
-use crate::assets::Assets;
-use crate::util::draw_hitbox;
+use crate::animation::SKIER_FLIP;
+use crate::assets::{Assets, Sprite};
+use crate::events::{EventBus, GameEvent};
+use crate::map::objects::ObjectKind;
+use crate::map::terrain::Surface;
+use crate::map::wrap_around;
+use crate::settings::Skin;
+use crate::util::{draw_hitbox, vec2_from_angle};
 
 #[derive(Debug)]
 pub struct Player {
     state: PlayerState,
-    image: Rc<Image>,
+    /// Where the skier is on the map
+    position: Vec2,
+    /// How far down the mountain the skier has got, in steps
+    distance: f32,
+    image: Rc<Sprite>,
     speed: f32,
+    /// Pixels moved each tick, which lags behind where the skier faces on
+    /// slippery or deep snow
+    velocity: Vec2,
+    /// What the skier is standing on
+    surface: Surface,
+    boosting: bool,
+    events: EventBus,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CollisionAction {
     Nothing,
     Fall,
+    /// Clipped the edge of something, losing speed
+    Glance,
+    /// Bounced over a mogul, losing speed
+    Hop,
+    /// Knocked off balance for a moment, losing most of the speed
+    Stumble,
+    /// Bounced off to the side, as off rocks
+    Deflect,
     JumpSmall,
     JumpLarge,
+    /// Get on the chairlift
+    Ride,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl CollisionAction {
+    /// How a hit turned out, in the words of the telemetry's collision
+    /// column
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollisionAction::Nothing => "nothing",
+            CollisionAction::Fall => "fall",
+            CollisionAction::Glance => "glance",
+            CollisionAction::Hop => "hop",
+            CollisionAction::Stumble => "stumble",
+            CollisionAction::Deflect => "deflect",
+            CollisionAction::JumpSmall => "jump_small",
+            CollisionAction::JumpLarge => "jump_large",
+            CollisionAction::Ride => "ride",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TrickType {
     Trick1,
     Trick2,
@@ -30,38 +78,219 @@ pub enum TrickType {
 }
 
 impl TrickType {
-    fn required_frames(&self) -> i8 {
+    /// Frames of a jump the trick needs to be landed
+    pub fn required_frames(&self) -> i8 {
         match self {
             TrickType::Trick1 => TRICK1_FRAMES,
             TrickType::Trick2 => TRICK2_FRAMES,
             TrickType::Flip => FLIP_FRAMES,
         }
     }
+
+    /// Style points awarded for landing the trick
+    pub fn style_points(&self) -> i32 {
+        match self {
+            TrickType::Trick1 => 10,
+            TrickType::Trick2 => 20,
+            TrickType::Flip => 50,
+        }
+    }
 }
 
 impl Player {
     pub const POSITION: [f32; 2] = [240., 200.];
     const PLAYER_SPEED_NORMAL: f32 = 3.;
+    const BOOST_MULTIPLIER: f32 = 1.5;
+    /// Speed regained every tick after hitting something
+    const SPEED_RECOVERY: f32 = 0.02;
+    /// Fastest a skier can go, boosting on ice, in pixels a tick
+    pub const MAX_SPEED: f32 = Self::PLAYER_SPEED_NORMAL * Self::BOOST_MULTIPLIER * 1.3;
 
     pub fn new(assets: &Assets) -> Self {
         Self {
             state: PlayerState::RightStop,
+            position: Self::POSITION.into(),
+            distance: 0.,
             image: assets.player.skier_r.clone(),
             speed: Self::PLAYER_SPEED_NORMAL,
+            velocity: Vec2::ZERO,
+            surface: Surface::Groomed,
+            boosting: false,
+            events: Default::default(),
         }
     }
 
     pub fn speed(&self) -> f32 {
-        self.speed
+        if self.boosting {
+            self.speed * Self::BOOST_MULTIPLIER
+        } else {
+            self.speed
+        }
     }
 
-    pub fn collision(&mut self, action: CollisionAction) {
-        self.state = match action {
-            CollisionAction::Fall if self.is_upright() => PlayerState::Fallen(FALLEN_FRAMES),
-            CollisionAction::JumpSmall if self.is_upright() => PlayerState::Jump(JUMP_FRAMES_SHORT),
-            CollisionAction::JumpLarge if self.is_upright() => PlayerState::Jump(JUMP_FRAMES_LONG),
-            _ => self.state,
+    /// Speed the player is actually travelling at. Zero when stopped.
+    pub fn current_speed(&self) -> f32 {
+        self.velocity.length()
+    }
+
+    pub fn surface(&self) -> Surface {
+        self.surface
+    }
+
+    /// Let the skier know what they're standing on
+    pub fn set_surface(&mut self, surface: Surface) {
+        self.surface = surface;
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    /// Put the player somewhere else on the map, e.g. at the start of a
+    /// course
+    pub fn place(&mut self, position: Vec2) {
+        self.position = position;
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// Move down the map for one tick. The snow only matters with skis on
+    /// it.
+    pub fn advance(&mut self) {
+        self.speed = (self.speed + Self::SPEED_RECOVERY).min(Self::PLAYER_SPEED_NORMAL);
+        let surface = if self.is_upright() || matches!(self.state, PlayerState::Stumbling(_)) {
+            self.surface
+        } else {
+            Surface::Groomed
+        };
+        let heading = self.direction().map(vec2_from_angle);
+        let target = heading.map_or(Vec2::ZERO, |heading| {
+            heading * self.speed() * surface.speed()
+        });
+        self.velocity = match surface.grip() {
+            grip if grip < 1. => self.velocity.lerp(target, grip),
+            _ => target,
+        };
+        // in steps, however fast the skier goes, that are shorter on slow snow
+        self.distance += heading.map_or(0., |heading| heading.y * surface.speed());
+        self.position += self.velocity;
+        self.position.x = wrap_around(self.position.x);
+    }
+
+    pub fn boost(&mut self, boosting: bool) {
+        self.boosting = boosting;
+    }
+
+    /// Events that happened to the player since the last call
+    pub fn events(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    /// Run into an object whose hitbox is `obstacle`. What happens depends
+    /// on how squarely and how fast the skier hit it.
+    pub fn collision(&mut self, kind: ObjectKind, action: CollisionAction, obstacle: Rect) {
+        if !self.is_upright() {
+            return;
+        }
+        let action = self.response(action, obstacle);
+        self.events.emit(GameEvent::Collided(kind, action));
+        let (state, event) = match action {
+            CollisionAction::Fall => (PlayerState::Fallen(FALLEN_FRAMES), GameEvent::Fell),
+            CollisionAction::Glance => {
+                self.speed *= GLANCE_SPEED;
+                return;
+            }
+            CollisionAction::Hop => {
+                self.speed *= HOP_SPEED;
+                (PlayerState::Jump(HOP_FRAMES), GameEvent::Hopped)
+            }
+            CollisionAction::Stumble => {
+                self.speed *= STUMBLE_SPEED;
+                self.state = PlayerState::Stumbling(STUMBLE_FRAMES);
+                return;
+            }
+            CollisionAction::Deflect => {
+                self.deflect(obstacle);
+                return;
+            }
+            CollisionAction::JumpSmall => {
+                // only long enough for a trick when going fast
+                let frames = if self.current_speed() > Self::PLAYER_SPEED_NORMAL {
+                    JUMP_FRAMES_FAST
+                } else {
+                    JUMP_FRAMES_SHORT
+                };
+                (PlayerState::Jump(frames), GameEvent::Jumped {
+                    large: false,
+                })
+            }
+            CollisionAction::JumpLarge => {
+                (PlayerState::Jump(JUMP_FRAMES_LONG), GameEvent::Jumped {
+                    large: true,
+                })
+            }
+            CollisionAction::Ride => (PlayerState::Riding, GameEvent::BoardedLift),
+            CollisionAction::Nothing => return,
         };
+        self.state = state;
+        self.events.emit(event);
+    }
+
+    /// What hitting an object that would `action` does to the skier. Hits on
+    /// the edge of an obstacle only glance off, and it takes heading
+    /// down the slope at full speed to be knocked over. Rocks only do that
+    /// when hit dead on.
+    fn response(&self, action: CollisionAction, obstacle: Rect) -> CollisionAction {
+        let hitbox = self.hitbox();
+        let overlap = (hitbox.right().min(obstacle.right()) - hitbox.left().max(obstacle.left()))
+            / hitbox.w.min(obstacle.w);
+        let charging = self.speed() >= Self::PLAYER_SPEED_NORMAL && self.is_heading_downhill();
+        match action {
+            CollisionAction::Fall if overlap < GLANCING_OVERLAP => CollisionAction::Glance,
+            CollisionAction::Fall if !charging => CollisionAction::Stumble,
+            CollisionAction::Deflect if charging && overlap >= HEAD_ON_OVERLAP => {
+                CollisionAction::Fall
+            }
+            action => action,
+        }
+    }
+
+    /// Bounce off to the side of `obstacle` furthest from the skier's
+    /// middle, clear of it, heading away
+    fn deflect(&mut self, obstacle: Rect) {
+        let hitbox = self.hitbox();
+        self.speed *= DEFLECT_SPEED;
+        if hitbox.center().x < obstacle.center().x {
+            self.position.x = obstacle.left() - hitbox.w - 1.;
+            self.state = PlayerState::Left45;
+        } else {
+            self.position.x = obstacle.right() + 1.;
+            self.state = PlayerState::Right45;
+        }
+        self.position.x = wrap_around(self.position.x);
+    }
+
+    pub fn is_riding(&self) -> bool {
+        self.state == PlayerState::Riding
+    }
+
+    /// Get off the chairlift at the top
+    pub fn dismount(&mut self) {
+        if self.is_riding() {
+            self.state = PlayerState::Sitting(SITTING_FRAMES);
+            self.events.emit(GameEvent::LiftTopReached);
+        }
+    }
+
+    pub fn is_eaten(&self) -> bool {
+        self.state == PlayerState::Eaten
+    }
+
+    /// Caught by the yeti. The run is over.
+    pub fn eaten(&mut self) {
+        self.state = PlayerState::Eaten;
     }
 
     pub fn do_trick(&mut self, trick: TrickType) {
@@ -71,16 +300,42 @@ impl Player {
             self.state = match trick {
                 TrickType::Trick1 => PlayerState::Trick1(jump_frame, success),
                 TrickType::Trick2 => PlayerState::Trick2(jump_frame, success),
-                TrickType::Flip => PlayerState::Flip(FlipSequence::Flip1(jump_frame), success),
+                TrickType::Flip => PlayerState::Flip(jump_frame, success),
             };
+            self.events.emit(GameEvent::TrickStarted(trick));
         }
     }
 
     pub fn maybe_next_state(&mut self, assets: &Assets) {
-        self.state = self.state.next_state();
+        let next = self.state.next_state();
+        match (self.state, next) {
+            (PlayerState::Jump(_), PlayerState::Downward) => self.events.emit(GameEvent::Landed),
+            (_, PlayerState::Downward) if self.is_tricking() => {
+                if let Some(trick) = self.state.trick() {
+                    self.events.emit(GameEvent::TrickSucceeded(trick));
+                }
+                self.events.emit(GameEvent::Landed);
+            }
+            (_, PlayerState::Fallen(_)) if self.is_tricking() => {
+                if let Some(trick) = self.state.trick() {
+                    self.events.emit(GameEvent::TrickFailed(trick));
+                }
+                self.events.emit(GameEvent::Fell);
+            }
+            _ => {}
+        }
+        self.state = next;
         self.image = self.image(assets);
     }
 
+    /// Change direction, letting listeners know if the skier turned
+    fn turn(&mut self, state: PlayerState) {
+        if state != self.state {
+            self.state = state;
+            self.events.emit(GameEvent::Turned);
+        }
+    }
+
     pub fn slide_left(&mut self) {
         self.state = match self.state {
             PlayerState::LeftMove => PlayerState::LeftStop,
@@ -89,7 +344,7 @@ impl Player {
     }
 
     pub fn left(&mut self) {
-        self.state = match self.state {
+        self.turn(match self.state {
             PlayerState::Downward => PlayerState::Left30,
             PlayerState::LeftStop => PlayerState::LeftMove,
             PlayerState::RightStop | PlayerState::RightMove => PlayerState::Right45,
@@ -98,7 +353,7 @@ impl Player {
             PlayerState::Right30 => PlayerState::Downward,
             PlayerState::Right45 => PlayerState::Right30,
             _ => self.state,
-        };
+        });
     }
 
     pub fn slide_right(&mut self) {
@@ -109,7 +364,7 @@ impl Player {
     }
 
     pub fn right(&mut self) {
-        self.state = match self.state {
+        self.turn(match self.state {
             PlayerState::Downward => PlayerState::Right30,
             PlayerState::LeftStop | PlayerState::LeftMove => PlayerState::Left45,
             PlayerState::RightStop => PlayerState::RightMove,
@@ -118,32 +373,69 @@ impl Player {
             PlayerState::Right30 => PlayerState::Right45,
             PlayerState::Right45 | PlayerState::RightMove => PlayerState::RightStop,
             _ => self.state,
-        };
+        });
+    }
+
+    /// Steer towards an angle, e.g. from an analog stick, picking the closest
+    /// direction the skier can face. `None` lets the skier slide to a stop.
+    pub fn steer(&mut self, angle: Option<f32>) {
+        if !self.is_upright() {
+            return;
+        }
+        match angle {
+            Some(angle) => self.turn(PlayerState::facing(angle)),
+            None => {
+                self.slide_left();
+                self.slide_right();
+            }
+        }
     }
 
     pub fn hitbox(&self) -> Rect {
         Rect::new(
-            Self::POSITION[0],
-            Self::POSITION[1] + self.image.height() as f32,
+            self.position.x,
+            self.position.y + self.image.height() as f32,
             self.image.width() as f32,
             5.,
         )
     }
 
-    pub fn draw(&self, ctx: &Context, assets: &Assets, canvas: &mut Canvas) {
+    /// Draw the skier. `offset` moves map coordinates to screen coordinates.
+    pub fn draw(
+        &self,
+        ctx: &Context,
+        assets: &Assets,
+        canvas: &mut Canvas,
+        skin: Skin,
+        offset: Vec2,
+    ) {
+        if self.is_eaten() {
+            return;
+        }
         let image = self.image(assets);
         #[cfg(debug_assertions)]
-        draw_hitbox(ctx, canvas, self.hitbox());
-        canvas.draw(image.as_ref(), DrawParam::default().dest(Self::POSITION));
+        {
+            let mut hitbox = self.hitbox();
+            hitbox.translate(offset);
+            draw_hitbox(ctx, canvas, hitbox);
+        }
+        canvas.draw(
+            image.as_ref(),
+            DrawParam::default()
+                .dest(self.position + offset)
+                .color(skin.tint()),
+        );
     }
 
     pub fn direction(&self) -> Option<f32> {
         match &self.state {
             PlayerState::Downward
+            | PlayerState::Stumbling(_)
             | PlayerState::Flip(..)
             | PlayerState::Jump(..)
             | PlayerState::Trick1(..)
             | PlayerState::Trick2(..) => Some(0.),
+            PlayerState::Riding => Some(PI),
             PlayerState::LeftMove => Some(3. * FRAC_PI_2),
             PlayerState::RightMove => Some(FRAC_PI_2),
             PlayerState::Left30 => Some(11. * FRAC_PI_6),
@@ -153,60 +445,46 @@ impl Player {
             PlayerState::LeftStop
             | PlayerState::RightStop
             | PlayerState::Fallen(_)
-            | PlayerState::Sitting(_) => None,
+            | PlayerState::Sitting(_)
+            | PlayerState::Eaten => None,
         }
     }
 
-    /// Return the angle in radians which is opposite of the angle that the
-    /// player is moving. None if player is not moving
-    pub fn opposite_direction(&self) -> Option<f32> {
-        match &self.state {
-            PlayerState::Downward
-            | PlayerState::Flip(..)
-            | PlayerState::Jump(_)
-            | PlayerState::Trick1(..)
-            | PlayerState::Trick2(..) => Some(PI),
-            PlayerState::LeftMove => Some(PI / 2.),
-            PlayerState::RightMove => Some(3. * PI / 2.),
-            PlayerState::Left30 => Some(5. * PI / 6.),
-            PlayerState::Left45 => Some(3. * PI / 4.),
-            PlayerState::Right30 => Some(7. * PI / 6.),
-            PlayerState::Right45 => Some(5. * PI / 4.),
-            PlayerState::LeftStop
-            | PlayerState::RightStop
-            | PlayerState::Fallen(_)
-            | PlayerState::Sitting(_) => None,
+    pub fn image(&self, assets: &Assets) -> Rc<Sprite> {
+        self.pose().image(assets).clone()
+    }
+
+    /// Name of what the skier is doing, e.g. for telemetry
+    pub fn state_name(&self) -> &'static str {
+        match self.state {
+            PlayerState::Downward => "downward",
+            PlayerState::Fallen(_) => "fallen",
+            PlayerState::Sitting(_) => "sitting",
+            PlayerState::Stumbling(_) => "stumbling",
+            PlayerState::Flip(..) => "flip",
+            PlayerState::Jump(_) => "jump",
+            PlayerState::LeftStop => "left_stop",
+            PlayerState::LeftMove => "left_move",
+            PlayerState::RightStop => "right_stop",
+            PlayerState::RightMove => "right_move",
+            PlayerState::Left30 => "left_30",
+            PlayerState::Left45 => "left_45",
+            PlayerState::Right30 => "right_30",
+            PlayerState::Right45 => "right_45",
+            PlayerState::Trick1(..) => "trick1",
+            PlayerState::Trick2(..) => "trick2",
+            PlayerState::Riding => "riding",
+            PlayerState::Eaten => "eaten",
         }
     }
 
-    fn image(&self, assets: &Assets) -> Rc<Image> {
-        let image = match self.state {
-            PlayerState::Downward => &assets.player.skier_down,
-            PlayerState::Fallen(_) => &assets.player.skier_fall,
-            PlayerState::Sitting(_) => &assets.player.skier_sit,
-            PlayerState::Flip(fs, _) => match fs {
-                FlipSequence::Flip1(_) => &assets.player.skier_flip,
-                FlipSequence::Flip2(_) => &assets.player.skier_flip2,
-                FlipSequence::Flip3(_) => &assets.player.skier_flip3,
-                FlipSequence::Flip4(_) => &assets.player.skier_flip4,
-            },
-            PlayerState::Jump(_) => &assets.player.skier_jump,
-            PlayerState::LeftStop => &assets.player.skier_l,
-            PlayerState::LeftMove => &assets.player.skier_l2,
-            PlayerState::RightStop => &assets.player.skier_r,
-            PlayerState::RightMove => &assets.player.skier_r2,
-            PlayerState::Left30 => &assets.player.skier_l30,
-            PlayerState::Left45 => &assets.player.skier_l45,
-            PlayerState::Right30 => &assets.player.skier_r30,
-            PlayerState::Right45 => &assets.player.skier_r45,
-            PlayerState::Trick1(..) => &assets.player.skier_trick,
-            PlayerState::Trick2(..) => &assets.player.skier_trick2,
-        };
-        image.clone()
+    /// What the skier looks like right now
+    pub fn pose(&self) -> Pose {
+        self.state.pose()
     }
 
     /// Moving, standing, but not jumping, tricking or fallen
-    fn is_upright(&self) -> bool {
+    pub fn is_upright(&self) -> bool {
         matches!(
             self.state,
             PlayerState::Downward
@@ -221,6 +499,18 @@ impl Player {
         )
     }
 
+    /// Skiing at most 45° either side of straight down the slope
+    fn is_heading_downhill(&self) -> bool {
+        matches!(
+            self.state,
+            PlayerState::Downward
+                | PlayerState::Left30
+                | PlayerState::Left45
+                | PlayerState::Right30
+                | PlayerState::Right45
+        )
+    }
+
     fn is_tricking(&self) -> bool {
         matches!(
             self.state,
@@ -238,22 +528,111 @@ impl Player {
     }
 }
 
+/// Which sprite the skier is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pose {
+    Down,
+    Fallen,
+    Sitting,
+    Flip1,
+    Flip2,
+    Flip3,
+    Flip4,
+    Jump,
+    LeftStop,
+    LeftMove,
+    RightStop,
+    RightMove,
+    Left30,
+    Left45,
+    Right30,
+    Right45,
+    Trick1,
+    Trick2,
+}
+
+impl Pose {
+    pub const ALL: [Pose; 18] = [
+        Pose::Down,
+        Pose::Fallen,
+        Pose::Sitting,
+        Pose::Flip1,
+        Pose::Flip2,
+        Pose::Flip3,
+        Pose::Flip4,
+        Pose::Jump,
+        Pose::LeftStop,
+        Pose::LeftMove,
+        Pose::RightStop,
+        Pose::RightMove,
+        Pose::Left30,
+        Pose::Left45,
+        Pose::Right30,
+        Pose::Right45,
+        Pose::Trick1,
+        Pose::Trick2,
+    ];
+
+    pub fn image<'a>(&self, assets: &'a Assets) -> &'a Rc<Sprite> {
+        let player = &assets.player;
+        match self {
+            Pose::Down => &player.skier_down,
+            Pose::Fallen => &player.skier_fall,
+            Pose::Sitting => &player.skier_sit,
+            Pose::Flip1 => &player.skier_flip,
+            Pose::Flip2 => &player.skier_flip2,
+            Pose::Flip3 => &player.skier_flip3,
+            Pose::Flip4 => &player.skier_flip4,
+            Pose::Jump => &player.skier_jump,
+            Pose::LeftStop => &player.skier_l,
+            Pose::LeftMove => &player.skier_l2,
+            Pose::RightStop => &player.skier_r,
+            Pose::RightMove => &player.skier_r2,
+            Pose::Left30 => &player.skier_l30,
+            Pose::Left45 => &player.skier_l45,
+            Pose::Right30 => &player.skier_r30,
+            Pose::Right45 => &player.skier_r45,
+            Pose::Trick1 => &player.skier_trick,
+            Pose::Trick2 => &player.skier_trick2,
+        }
+    }
+}
+
 type Frames = i8;
 
 const FALLEN_FRAMES: i8 = 60;
 const SITTING_FRAMES: i8 = 60;
+const STUMBLE_FRAMES: i8 = 20;
 const JUMP_FRAMES_SHORT: i8 = 20;
+/// Off a bump at speed
+const JUMP_FRAMES_FAST: i8 = 48;
+/// Over a mogul
+const HOP_FRAMES: i8 = 8;
 const JUMP_FRAMES_LONG: i8 = 60;
 const TRICK1_FRAMES: i8 = 40;
 const TRICK2_FRAMES: i8 = 40;
 const FLIP_FRAMES: i8 = 50;
 
-#[derive(Debug, Clone, Copy)]
+/// Least of an obstacle's or the skier's width, whichever is narrower, that
+/// has to be hit for more than a glance
+const GLANCING_OVERLAP: f32 = 0.3;
+/// How much of it has to be hit to be dead on
+const HEAD_ON_OVERLAP: f32 = 0.9;
+/// Speed kept after each kind of hit
+const GLANCE_SPEED: f32 = 0.6;
+const HOP_SPEED: f32 = 0.8;
+const STUMBLE_SPEED: f32 = 0.4;
+const DEFLECT_SPEED: f32 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum PlayerState {
     Downward,
     Fallen(Frames),
     Sitting(Frames),
-    Flip(FlipSequence, bool),
+    /// Off balance, carried straight down without steering
+    Stumbling(Frames),
+    Flip(Frames, bool),
     Jump(Frames),
     LeftStop,
     LeftMove,
@@ -265,13 +644,68 @@ enum PlayerState {
     Right45,
     Trick1(Frames, bool),
     Trick2(Frames, bool),
+    /// On the chairlift, going up
+    Riding,
+    Eaten,
 }
 
 impl PlayerState {
+    /// The upright state closest to the given angle. Pointing the stick
+    /// uphill stops the skier.
+    fn facing(angle: f32) -> PlayerState {
+        let degrees = angle.to_degrees();
+        let left = degrees < 0.;
+        match degrees.abs() {
+            d if d < 15. => PlayerState::Downward,
+            d if d < 37.5 && left => PlayerState::Left30,
+            d if d < 37.5 => PlayerState::Right30,
+            d if d < 67.5 && left => PlayerState::Left45,
+            d if d < 67.5 => PlayerState::Right45,
+            d if d < 135. && left => PlayerState::LeftMove,
+            d if d < 135. => PlayerState::RightMove,
+            _ if left => PlayerState::LeftStop,
+            _ => PlayerState::RightStop,
+        }
+    }
+
+    fn pose(&self) -> Pose {
+        match self {
+            PlayerState::Downward => Pose::Down,
+            PlayerState::Fallen(_) => Pose::Fallen,
+            PlayerState::Sitting(_) | PlayerState::Riding | PlayerState::Eaten => Pose::Sitting,
+            // arms out, trying to stay up
+            PlayerState::Stumbling(_) => Pose::Jump,
+            // timed so the flip comes round just as the skier lands
+            PlayerState::Flip(f, _) => *SKIER_FLIP.frame_at((FLIP_FRAMES - f).max(0) as u32),
+            PlayerState::Jump(_) => Pose::Jump,
+            PlayerState::LeftStop => Pose::LeftStop,
+            PlayerState::LeftMove => Pose::LeftMove,
+            PlayerState::RightStop => Pose::RightStop,
+            PlayerState::RightMove => Pose::RightMove,
+            PlayerState::Left30 => Pose::Left30,
+            PlayerState::Left45 => Pose::Left45,
+            PlayerState::Right30 => Pose::Right30,
+            PlayerState::Right45 => Pose::Right45,
+            PlayerState::Trick1(..) => Pose::Trick1,
+            PlayerState::Trick2(..) => Pose::Trick2,
+        }
+    }
+
+    /// The trick being performed, if any
+    fn trick(&self) -> Option<TrickType> {
+        match self {
+            PlayerState::Trick1(..) => Some(TrickType::Trick1),
+            PlayerState::Trick2(..) => Some(TrickType::Trick2),
+            PlayerState::Flip(..) => Some(TrickType::Flip),
+            _ => None,
+        }
+    }
+
     fn next_state(self) -> PlayerState {
         match self {
             PlayerState::Fallen(f) if f > 0 => PlayerState::Fallen(f - 1),
             PlayerState::Sitting(f) if f > 0 => PlayerState::Sitting(f - 1),
+            PlayerState::Stumbling(f) if f > 0 => PlayerState::Stumbling(f - 1),
             PlayerState::Jump(f) if f > 0 => PlayerState::Jump(f - 1),
             PlayerState::Trick1(f, s) if f > 0 => PlayerState::Trick1(f - 1, s),
             PlayerState::Trick1(_, s) => {
@@ -289,9 +723,18 @@ impl PlayerState {
                     PlayerState::Fallen(FALLEN_FRAMES)
                 }
             }
-            PlayerState::Flip(flip, s) => flip.next_state(s),
+            PlayerState::Flip(f, s) if f > 0 => PlayerState::Flip(f - 1, s),
+            PlayerState::Flip(_, s) => {
+                if s {
+                    PlayerState::Downward
+                } else {
+                    PlayerState::Fallen(FALLEN_FRAMES)
+                }
+            }
             PlayerState::Fallen(_) => PlayerState::Sitting(SITTING_FRAMES),
-            PlayerState::Sitting(_) | PlayerState::Jump(_) => PlayerState::Downward,
+            PlayerState::Sitting(_) | PlayerState::Stumbling(_) | PlayerState::Jump(_) => {
+                PlayerState::Downward
+            }
             PlayerState::Downward
             | PlayerState::LeftStop
             | PlayerState::LeftMove
@@ -300,75 +743,138 @@ impl PlayerState {
             | PlayerState::Left30
             | PlayerState::Left45
             | PlayerState::Right30
-            | PlayerState::Right45 => self,
+            | PlayerState::Right45
+            | PlayerState::Riding
+            | PlayerState::Eaten => self,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum FlipSequence {
-    Flip1(Frames),
-    Flip2(Frames),
-    Flip3(Frames),
-    Flip4(Frames),
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl FlipSequence {
-    fn next_state(self, success: bool) -> PlayerState {
-        PlayerState::Flip(
-            match self {
-                FlipSequence::Flip1(f) => {
-                    if f == (FLIP_FRAMES / 4) * 3 {
-                        FlipSequence::Flip2(f)
-                    } else if f <= 0 {
-                        if !success {
-                            return PlayerState::Fallen(FALLEN_FRAMES);
-                        } else {
-                            return PlayerState::Downward;
-                        }
-                    } else {
-                        FlipSequence::Flip1(f - 1)
-                    }
-                }
-                FlipSequence::Flip2(f) => {
-                    if f == (FLIP_FRAMES / 4) * 2 {
-                        FlipSequence::Flip3(f)
-                    } else if f <= 0 {
-                        if !success {
-                            return PlayerState::Fallen(FALLEN_FRAMES);
-                        } else {
-                            return PlayerState::Downward;
-                        }
-                    } else {
-                        FlipSequence::Flip2(f - 1)
-                    }
-                }
-                FlipSequence::Flip3(f) => {
-                    if f == (FLIP_FRAMES / 4) * 2 {
-                        FlipSequence::Flip4(f)
-                    } else if f <= 0 {
-                        if !success {
-                            return PlayerState::Fallen(FALLEN_FRAMES);
-                        } else {
-                            return PlayerState::Downward;
-                        }
-                    } else {
-                        FlipSequence::Flip3(f - 1)
-                    }
-                }
-                FlipSequence::Flip4(f) => {
-                    if f == 0 {
-                        if !success {
-                            return PlayerState::Fallen(FALLEN_FRAMES);
-                        } else {
-                            return PlayerState::Downward;
-                        }
-                    } else {
-                        FlipSequence::Flip4(f - 1)
-                    }
-                }
-            },
-            success,
-        )
+    #[test]
+    fn can_face_stick_angle() {
+        assert_eq!(PlayerState::facing(0.), PlayerState::Downward);
+        assert_eq!(PlayerState::facing(FRAC_PI_6), PlayerState::Right30);
+        assert_eq!(PlayerState::facing(-FRAC_PI_4), PlayerState::Left45);
+        assert_eq!(PlayerState::facing(FRAC_PI_2), PlayerState::RightMove);
+        assert_eq!(PlayerState::facing(-FRAC_PI_2), PlayerState::LeftMove);
+        assert_eq!(PlayerState::facing(PI), PlayerState::RightStop);
+        assert_eq!(PlayerState::facing(-3. * FRAC_PI_4), PlayerState::LeftStop);
+    }
+
+    #[test]
+    fn hits_depend_on_how_squarely_and_fast() {
+        let assets = Assets::headless();
+        let mut player = Player::new(&assets);
+        player.steer(Some(0.));
+        player.maybe_next_state(&assets);
+        let hitbox = player.hitbox();
+        let edge = Rect::new(hitbox.right() - 2., hitbox.y, 20., 5.);
+        assert_eq!(
+            player.response(CollisionAction::Fall, edge),
+            CollisionAction::Glance
+        );
+        assert_eq!(
+            player.response(CollisionAction::Fall, hitbox),
+            CollisionAction::Fall
+        );
+        assert_eq!(
+            player.response(CollisionAction::Deflect, hitbox),
+            CollisionAction::Fall
+        );
+
+        let rock = Rect::new(hitbox.center().x, hitbox.y, 20., 5.);
+        player.collision(ObjectKind::Rock, CollisionAction::Deflect, rock);
+        assert_eq!(player.state, PlayerState::Left45);
+        assert!(!player.hitbox().overlaps(&rock));
+        assert!(player.speed() < Player::PLAYER_SPEED_NORMAL);
+        // too slow to be knocked over
+        let hitbox = player.hitbox();
+        player.collision(ObjectKind::Tree, CollisionAction::Fall, hitbox);
+        assert_eq!(player.state, PlayerState::Stumbling(STUMBLE_FRAMES));
+    }
+
+    #[test]
+    fn moguls_slow_and_bumps_need_speed_for_tricks() {
+        let assets = Assets::headless();
+        let heading_down = || {
+            let mut player = Player::new(&assets);
+            player.steer(Some(0.));
+            player.maybe_next_state(&assets);
+            player
+        };
+        let mut player = heading_down();
+        let hitbox = player.hitbox();
+        player.collision(ObjectKind::Mogul, CollisionAction::Hop, hitbox);
+        assert_eq!(player.jump_frame(), Some(HOP_FRAMES));
+        assert!(player.speed() < Player::PLAYER_SPEED_NORMAL);
+        let events: Vec<_> = player.events().drain().collect();
+        assert!(events.contains(&GameEvent::Hopped));
+        assert!(!events.iter().any(|e| matches!(e, GameEvent::Jumped { .. })));
+        while player.jump_frame().is_some() {
+            player.maybe_next_state(&assets);
+        }
+        assert!(player.events().drain().any(|e| e == GameEvent::Landed));
+
+        let mut player = heading_down();
+        player.collision(ObjectKind::Bump, CollisionAction::JumpSmall, hitbox);
+        let air = player.jump_frame().unwrap();
+        assert!(air < TrickType::Trick1.required_frames());
+
+        let mut player = heading_down();
+        player.boost(true);
+        player.advance();
+        player.collision(ObjectKind::Bump, CollisionAction::JumpSmall, hitbox);
+        let air = player.jump_frame().unwrap();
+        assert!(air >= TrickType::Trick2.required_frames());
+    }
+
+    #[test]
+    fn surfaces_change_speed_and_turning() {
+        let assets = Assets::headless();
+        let mut player = Player::new(&assets);
+        player.steer(Some(0.));
+        player.maybe_next_state(&assets);
+        player.set_surface(Surface::Powder);
+        for _ in 0..30 {
+            player.advance();
+        }
+        let powder = player.current_speed();
+        assert!(powder < Player::PLAYER_SPEED_NORMAL * 0.7);
+        // deep snow doesn't soften a tree hit head on
+        assert_eq!(
+            player.response(CollisionAction::Fall, player.hitbox()),
+            CollisionAction::Fall
+        );
+
+        player.set_surface(Surface::Ice);
+        for _ in 0..60 {
+            player.advance();
+        }
+        assert!(player.current_speed() > Player::PLAYER_SPEED_NORMAL);
+        // still sliding downhill a few ticks after turning across the slope
+        player.steer(Some(FRAC_PI_2));
+        for _ in 0..5 {
+            player.advance();
+        }
+        assert!(player.velocity.y > player.velocity.x);
+    }
+
+    #[test]
+    fn flip_goes_through_every_pose() {
+        assert_eq!(SKIER_FLIP.duration(), FLIP_FRAMES as u32);
+        let mut state = PlayerState::Flip(JUMP_FRAMES_LONG, true);
+        let mut poses = vec![];
+        while state != PlayerState::Downward {
+            let pose = state.pose();
+            if poses.last() != Some(&pose) {
+                poses.push(pose);
+            }
+            state = state.next_state();
+        }
+        assert_eq!(poses, [Pose::Flip1, Pose::Flip2, Pose::Flip3, Pose::Flip4]);
     }
 }
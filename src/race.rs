@@ -0,0 +1,68 @@
+/// Split screen race to the finish line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Race {
+    /// Seconds each skier took to finish, once they have
+    finish_times: Vec<Option<f32>>,
+}
+
+impl Race {
+    pub fn new(skiers: usize) -> Self {
+        Self {
+            finish_times: vec![None; skiers],
+        }
+    }
+
+    /// Record the skier's time, unless they've already finished
+    pub fn finish(&mut self, skier: usize, time: f32) {
+        let finish_time = &mut self.finish_times[skier];
+        if finish_time.is_none() {
+            *finish_time = Some(time);
+        }
+    }
+
+    /// First skier over the line
+    pub fn winner(&self) -> Option<usize> {
+        self.finish_times
+            .iter()
+            .enumerate()
+            .filter_map(|(i, time)| time.map(|t| (i, t)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Text announcing the winner and everyone's times
+    pub fn result(&self) -> Option<String> {
+        let winner = self.winner()?;
+        let mut lines = vec![format!("Player {} wins!", winner + 1)];
+        for (i, time) in self.finish_times.iter().enumerate() {
+            let time = time.map_or("-".to_string(), format_time);
+            lines.push(format!("Player {}: {time}", i + 1));
+        }
+        Some(lines.join("\n"))
+    }
+}
+
+/// Seconds as minutes:seconds.hundredths
+pub fn format_time(seconds: f32) -> String {
+    format!("{}:{:05.2}", (seconds / 60.) as u32, seconds % 60.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_over_the_line_wins() {
+        let mut race = Race::new(2);
+        assert_eq!(race.result(), None);
+        race.finish(1, 75.5);
+        race.finish(1, 90.);
+        assert_eq!(race.winner(), Some(1));
+        assert_eq!(
+            race.result().unwrap(),
+            "Player 2 wins!\nPlayer 1: -\nPlayer 2: 1:15.50"
+        );
+        race.finish(0, 80.);
+        assert_eq!(race.winner(), Some(1));
+    }
+}
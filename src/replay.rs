@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::events::GameEvent;
+use crate::headless::Simulation;
+use crate::map::Course;
+use crate::player::{Player, TrickType};
+use crate::settings::Difficulty;
+
+/// Something a player did to their skier
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "input", rename_all = "snake_case")]
+pub enum PlayerInput {
+    Left,
+    Right,
+    SlideLeft,
+    SlideRight,
+    Trick {
+        trick: TrickType,
+    },
+    Boost {
+        boosting: bool,
+    },
+    /// Steer towards an angle, from an analog stick
+    Steer {
+        angle: f32,
+    },
+    /// Let go of the analog stick
+    Center,
+}
+
+impl PlayerInput {
+    pub fn apply(self, player: &mut Player) {
+        match self {
+            PlayerInput::Left => player.left(),
+            PlayerInput::Right => player.right(),
+            PlayerInput::SlideLeft => player.slide_left(),
+            PlayerInput::SlideRight => player.slide_right(),
+            PlayerInput::Trick { trick } => player.do_trick(trick),
+            PlayerInput::Boost { boosting } => player.boost(boosting),
+            PlayerInput::Steer { angle } => player.steer(Some(angle)),
+            PlayerInput::Center => player.steer(None),
+        }
+    }
+}
+
+/// An input and the tick it came before
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimedInput {
+    pub tick: u32,
+    #[serde(flatten)]
+    pub input: PlayerInput,
+}
+
+/// Everything needed to ski a run again: the slope and what the player did
+/// on it. The simulation is deterministic, so playing the inputs back gives
+/// the same run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    /// Course the skier started on, instead of the top of the slope
+    #[serde(default)]
+    pub course: Option<Course>,
+    #[serde(default)]
+    pub inputs: Vec<TimedInput>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, difficulty: Difficulty, course: Option<Course>) -> Self {
+        Self {
+            seed,
+            difficulty,
+            course,
+            inputs: vec![],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string(self)?;
+        std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+    }
+
+    pub fn record(&mut self, tick: u32, input: PlayerInput) {
+        self.inputs.push(TimedInput { tick, input });
+    }
+
+    /// The replay's slope, with the skier where the run started
+    pub fn start(&self) -> Simulation {
+        let mut sim = Simulation::new(self.seed, self.difficulty);
+        if let Some(course) = self.course {
+            sim.skier.player.place(course.start());
+        }
+        sim
+    }
+
+    /// Apply the inputs of the next tick and simulate it
+    pub fn step(&self, sim: &mut Simulation) -> Vec<GameEvent> {
+        for input in self.inputs_at(sim.tick()) {
+            input.apply(&mut sim.skier.player);
+        }
+        sim.step()
+    }
+
+    /// Inputs to apply before simulating `tick`
+    pub fn inputs_at(&self, tick: u32) -> impl Iterator<Item = PlayerInput> + '_ {
+        let start = self.inputs.partition_point(|i| i.tick < tick);
+        self.inputs[start..]
+            .iter()
+            .take_while(move |i| i.tick == tick)
+            .map(|i| i.input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_skis_the_same_run() {
+        let mut replay = Replay::new(3, Difficulty::Hard, Some(Course::Slalom));
+        replay.record(0, PlayerInput::Left);
+        replay.record(0, PlayerInput::Left);
+        replay.record(0, PlayerInput::Left);
+        replay.record(90, PlayerInput::Boost { boosting: true });
+        replay.record(200, PlayerInput::Steer { angle: 0.25 });
+        replay.record(260, PlayerInput::Center);
+        replay.record(300, PlayerInput::Trick {
+            trick: TrickType::Flip,
+        });
+
+        let toml = toml::to_string(&replay).unwrap();
+        let loaded: Replay = toml::from_str(&toml).unwrap();
+        assert_eq!(loaded, replay);
+        assert_eq!(loaded.inputs_at(0).count(), 3);
+        assert_eq!(loaded.inputs_at(1).count(), 0);
+
+        let run = |replay: &Replay| {
+            let mut sim = replay.start();
+            for _ in 0..400 {
+                replay.step(&mut sim);
+            }
+            sim.skier.player.position()
+        };
+        let position = run(&replay);
+        assert!(position.x < 0., "still on the slalom course");
+        assert_eq!(run(&loaded), position);
+    }
+}
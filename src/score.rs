@@ -0,0 +1,46 @@
+use crate::events::{GameEvent, Subscriber};
+
+/// Points earned during a run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub style: i32,
+    pub gates_passed: u32,
+    pub gates_missed: u32,
+}
+
+impl Subscriber for Score {
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::TrickSucceeded(trick) => self.style += trick.style_points(),
+            GameEvent::GatePassed => self.gates_passed += 1,
+            GameEvent::GateMissed => self.gates_missed += 1,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::TrickType;
+
+    #[test]
+    fn scores_tricks_and_gates() {
+        let mut score = Score::default();
+        for event in [
+            GameEvent::TrickStarted(TrickType::Flip),
+            GameEvent::TrickSucceeded(TrickType::Flip),
+            GameEvent::TrickFailed(TrickType::Trick1),
+            GameEvent::GatePassed,
+            GameEvent::GateMissed,
+            GameEvent::GatePassed,
+        ] {
+            score.on_event(&event);
+        }
+        assert_eq!(score, Score {
+            style: 50,
+            gates_passed: 2,
+            gates_missed: 1,
+        });
+    }
+}
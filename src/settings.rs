@@ -0,0 +1,145 @@
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub difficulty: Difficulty,
+    /// Sound effects, 0 (muted) to `MAX_VOLUME`
+    pub volume: u8,
+    /// Background music, 0 (muted) to `MAX_VOLUME`
+    pub music_volume: u8,
+    pub fullscreen: bool,
+    pub show_minimap: bool,
+    pub skin: Skin,
+    /// 1, or 2 for a split screen race
+    pub players: u8,
+    /// Least of the slope each skier sees, in pixels. The window shows it
+    /// scaled up by the biggest whole number that fits, and any room left
+    /// over shows more of the slope.
+    pub view_width: u16,
+    pub view_height: u16,
+}
+
+impl Settings {
+    pub const MAX_VOLUME: u8 = 10;
+    pub const MAX_PLAYERS: u8 = 2;
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Normal,
+            volume: 8,
+            music_volume: 5,
+            fullscreen: false,
+            show_minimap: true,
+            skin: Skin::Classic,
+            players: 1,
+            view_width: 480,
+            view_height: 640,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal | Difficulty::Hard => Difficulty::Hard,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        match self {
+            Difficulty::Easy | Difficulty::Normal => Difficulty::Easy,
+            Difficulty::Hard => Difficulty::Normal,
+        }
+    }
+
+    /// Multiplier for how likely an obstacle is placed on a course
+    pub fn obstacle_density(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.7,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.3,
+        }
+    }
+}
+
+/// How the skier is drawn. Everything but `Classic` is unlocked by an
+/// achievement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Skin {
+    Classic,
+    Gold,
+    Ice,
+    Crimson,
+}
+
+impl Skin {
+    pub const ALL: [Skin; 4] = [Skin::Classic, Skin::Gold, Skin::Ice, Skin::Crimson];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Skin::Classic => "classic",
+            Skin::Gold => "gold",
+            Skin::Ice => "ice",
+            Skin::Crimson => "crimson",
+        }
+    }
+
+    /// Colour multiplied with the skier sprites
+    pub fn tint(&self) -> Color {
+        match self {
+            Skin::Classic => Color::WHITE,
+            Skin::Gold => Color::from_rgb(255, 210, 80),
+            Skin::Ice => Color::from_rgb(150, 210, 255),
+            Skin::Crimson => Color::from_rgb(230, 90, 90),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_steps_stop_at_the_ends() {
+        assert_eq!(Difficulty::Hard.next(), Difficulty::Hard);
+        assert_eq!(Difficulty::Easy.prev(), Difficulty::Easy);
+        for difficulty in Difficulty::ALL {
+            if difficulty != Difficulty::Hard {
+                assert_eq!(difficulty.next().prev(), difficulty);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_settings_are_defaults() {
+        let settings: Settings = toml::from_str("volume = 3\nskin = \"gold\"\n").unwrap();
+        assert_eq!(settings.volume, 3);
+        assert_eq!(settings.skin, Skin::Gold);
+        assert_eq!(settings.players, Settings::default().players);
+        assert!(toml::from_str::<Settings>("skin = \"neon\"\n").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use ggez::glam::Vec2;
+use ggez::graphics::Rect;
+
+use crate::assets::Assets;
+use crate::hud::Hud;
+use crate::map::{Map, Progress, LIFT_TOP};
+use crate::player::Player;
+use crate::score::Score;
+use crate::stats::RunStats;
+use crate::yeti::Yeti;
+
+/// How far down the viewport the skier is kept, 200px down the original
+/// 640px window
+const ANCHOR_HEIGHT: f32 = 0.3125;
+
+/// Where on the screen the skier being followed is drawn: in the middle,
+/// near the top so they can see what's coming
+pub fn anchor(viewport: Rect) -> Vec2 {
+    Vec2::new(
+        viewport.x + viewport.w / 2.,
+        viewport.y + viewport.h * ANCHOR_HEIGHT,
+    )
+}
+
+/// Everything that belongs to one skier on the slope
+pub struct Skier {
+    pub player: Player,
+    pub progress: Progress,
+    /// Each skier has their own yeti to worry about
+    pub yeti: Yeti,
+    pub hud: Hud,
+    pub score: Score,
+    pub stats: RunStats,
+}
+
+impl Skier {
+    pub fn new(assets: &Assets, show_minimap: bool) -> Self {
+        Self {
+            player: Player::new(assets),
+            progress: Default::default(),
+            yeti: Default::default(),
+            hud: Hud::default().set_show_minimap(show_minimap),
+            score: Default::default(),
+            stats: Default::default(),
+        }
+    }
+
+    /// Simulate a tick for skier number `index`
+    pub fn update(&mut self, index: usize, map: &mut Map, assets: &Assets) {
+        if let Some((kind, action, hitbox)) = map.check_collision(&self.player, index) {
+            self.player.collision(kind, action, hitbox);
+        }
+        if self.player.is_riding() && self.player.position().y <= LIFT_TOP {
+            self.player.dismount();
+        }
+        self.player
+            .set_surface(map.surface_at(self.player.hitbox().center().into()));
+        self.player.advance();
+        map.update_progress(&mut self.progress, index, &mut self.player);
+        self.yeti.update(&mut self.player);
+        self.player.maybe_next_state(assets);
+        self.stats.update(&self.player, &self.progress);
+    }
+
+    /// Finished, or eaten by the yeti
+    pub fn is_over(&self) -> bool {
+        self.progress.is_finished() || self.player.is_eaten()
+    }
+
+    /// Offset from map to screen coordinates that keeps the skier in place
+    /// in the viewport
+    pub fn camera(&self, viewport: Rect) -> Vec2 {
+        anchor(viewport) - self.player.position()
+    }
+
+    /// The part of the map seen through the viewport
+    pub fn view(&self, viewport: Rect) -> Rect {
+        let camera = self.camera(viewport);
+        Rect::new(
+            viewport.x - camera.x,
+            viewport.y - camera.y,
+            viewport.w,
+            viewport.h,
+        )
+    }
+}
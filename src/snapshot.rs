@@ -0,0 +1,336 @@
+//! Drawing the game on the CPU, for screenshots without a window or a GPU
+
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+use ggez::glam::Vec2;
+use ggez::graphics::{Color, Rect};
+use image::{Rgba, RgbaImage};
+
+use crate::assets::{Assets, Sprite, ATLAS_PNG};
+use crate::map::Map;
+use crate::settings::Skin;
+use crate::skier::Skier;
+
+/// Space between the edge of the frame and the HUD, as in the game
+const MARGIN: i32 = 8;
+const PADDING: i32 = 6;
+/// Pixels a glyph of the font takes up, before scaling
+const GLYPH_WIDTH: i32 = 5;
+const GLYPH_HEIGHT: i32 = 7;
+/// From one glyph or line of text to the next
+const ADVANCE: i32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: i32 = GLYPH_HEIGHT + 3;
+
+/// Draws a skier's view of the slope into an RGBA image, from the decoded
+/// atlas
+pub struct Renderer {
+    atlas: RgbaImage,
+    show_hud: bool,
+}
+
+impl Renderer {
+    pub fn new() -> Result<Self> {
+        let atlas = image::load_from_memory(ATLAS_PNG).context("decoding the atlas")?;
+        Ok(Self {
+            atlas: atlas.to_rgba8(),
+            show_hud: true,
+        })
+    }
+
+    pub fn show_hud(mut self, show_hud: bool) -> Self {
+        self.show_hud = show_hud;
+        self
+    }
+
+    /// Draw what `skier` sees of `map` on a frame `width` by `height` pixels,
+    /// as the game draws a viewport of that size
+    pub fn render(
+        &self,
+        assets: &Assets,
+        map: &Map,
+        skier: &Skier,
+        width: u32,
+        height: u32,
+    ) -> RgbaImage {
+        let mut frame = RgbaImage::from_pixel(width, height, Rgba([255; 4]));
+        let viewport = Rect::new(0., 0., width as f32, height as f32);
+        let camera = skier.camera(viewport);
+
+        for (surface, area) in map.visible_terrain(skier.view(viewport), camera) {
+            let rect = [area.x, area.y, area.w, area.h].map(|v| v.round() as i32);
+            fill_rect(&mut frame, rect, rgba(surface.color()));
+        }
+        for (object, sprite, shift) in map.visible(skier.view(viewport), camera, 0) {
+            self.blit(
+                &mut frame,
+                sprite,
+                Vec2::from(object.position) + shift,
+                Color::WHITE,
+            );
+        }
+        for line in map.lines() {
+            let [start, end] = line.points.map(|p| Vec2::from(p) + camera);
+            draw_line(&mut frame, start, end, line.width, line.color);
+        }
+        if !skier.player.is_eaten() {
+            let sprite = skier.player.image(assets);
+            let position = skier.player.position() + camera;
+            self.blit(&mut frame, &sprite, position, Skin::Classic.tint());
+        }
+        if let Some((sprite, position)) = skier.yeti.sprite(assets) {
+            self.blit(&mut frame, sprite, position + camera, Color::WHITE);
+        }
+        if self.show_hud {
+            draw_hud(&mut frame, skier);
+        }
+        frame
+    }
+
+    /// Draw `sprite` with its top left at `position`, multiplied by `tint`
+    fn blit(&self, frame: &mut RgbaImage, sprite: &Sprite, position: Vec2, tint: Color) {
+        let [x, y, width, height] = sprite.rect();
+        let (left, top) = (position.x.round() as i32, position.y.round() as i32);
+        let tint = rgba(tint);
+        for sy in 0..height {
+            for sx in 0..width {
+                let Rgba(mut pixel) = *self.atlas.get_pixel(x + sx, y + sy);
+                for (channel, tint) in pixel.iter_mut().zip(tint) {
+                    *channel = (*channel as u32 * tint as u32 / 255) as u8;
+                }
+                blend(frame, left + sx as i32, top + sy as i32, pixel);
+            }
+        }
+    }
+}
+
+/// Save a frame as a PNG
+pub fn save(frame: &RgbaImage, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    frame
+        .save(path)
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    let (r, g, b, a) = color.to_rgba();
+    [r, g, b, a]
+}
+
+/// Draw `color` over the pixel at `x`, `y`, if it's in the frame
+fn blend(frame: &mut RgbaImage, x: i32, y: i32, [r, g, b, a]: [u8; 4]) {
+    if x < 0 || y < 0 || x >= frame.width() as i32 || y >= frame.height() as i32 || a == 0 {
+        return;
+    }
+    let Rgba(pixel) = frame.get_pixel_mut(x as u32, y as u32);
+    let a = a as u32;
+    for (channel, source) in pixel.iter_mut().zip([r, g, b]) {
+        *channel = ((source as u32 * a + *channel as u32 * (255 - a)) / 255) as u8;
+    }
+    pixel[3] = (a + pixel[3] as u32 * (255 - a) / 255) as u8;
+}
+
+fn fill_rect(frame: &mut RgbaImage, rect: [i32; 4], color: [u8; 4]) {
+    let [x, y, width, height] = rect;
+    for py in y..y + height {
+        for px in x..x + width {
+            blend(frame, px, py, color);
+        }
+    }
+}
+
+/// White with a black border, like the HUD's panels
+fn draw_panel(frame: &mut RgbaImage, rect: [i32; 4]) {
+    let [x, y, width, height] = rect;
+    fill_rect(frame, rect, [0, 0, 0, 255]);
+    fill_rect(frame, [x + 1, y + 1, width - 2, height - 2], [255; 4]);
+}
+
+/// A line `width` pixels thick, one square of it at every pixel step
+fn draw_line(frame: &mut RgbaImage, start: Vec2, end: Vec2, width: f32, color: Color) {
+    let color = rgba(color);
+    let size = width.ceil().max(1.) as i32;
+    let bounds = Rect::new(
+        -width,
+        -width,
+        frame.width() as f32 + width * 2.,
+        frame.height() as f32 + width * 2.,
+    );
+    let steps = (end - start).abs().max_element().ceil().max(1.) as u32;
+    for step in 0..=steps {
+        let point = start.lerp(end, step as f32 / steps as f32);
+        if bounds.contains(point) {
+            let (x, y) = (point.x.round() as i32, point.y.round() as i32);
+            fill_rect(frame, [x - size / 2, y - size / 2, size, size], color);
+        }
+    }
+}
+
+/// The panel of the HUD, and the notice and toast when there are any. Text is
+/// in a small pixel font, as there's no font renderer without a GPU.
+fn draw_hud(frame: &mut RgbaImage, skier: &Skier) {
+    let hud = &skier.hud;
+    let (width, height) = (frame.width() as i32, frame.height() as i32);
+    // the same font size as the game's, to the nearest whole scale
+    let scale = ((height as f32 / 40.).clamp(10., 20.) / (LINE_HEIGHT as f32)).round() as i32;
+    let scale = scale.max(1);
+
+    let lines = hud.lines();
+    let text_width = |lines: &[&str]| {
+        let chars = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32;
+        chars * ADVANCE * scale
+    };
+    let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+    let panel_width = text_width(&lines) + PADDING * 2;
+    let panel = [
+        width - panel_width - MARGIN,
+        MARGIN,
+        panel_width,
+        lines.len() as i32 * LINE_HEIGHT * scale + PADDING * 2,
+    ];
+    draw_panel(frame, panel);
+    draw_text(frame, &lines, panel[0] + PADDING, panel[1] + PADDING, scale);
+
+    if let Some((notice, _)) = hud.notice {
+        let notice_scale = (scale * 3 / 2).max(1);
+        let notice_width = text_width(&[notice]) / scale * notice_scale;
+        let x = (width - notice_width) / 2;
+        draw_text(frame, &[notice], x, height / 5, notice_scale);
+    }
+
+    if let Some((achievement, _)) = hud.toast {
+        let first = format!("Achievement unlocked: {}", achievement.name());
+        let lines = [first.as_str(), achievement.description()];
+        let size = [text_width(&lines), 2 * LINE_HEIGHT * scale];
+        let toast = [
+            (width - size[0]) / 2 - PADDING,
+            height - size[1] - PADDING * 2 - MARGIN,
+            size[0] + PADDING * 2,
+            size[1] + PADDING * 2,
+        ];
+        draw_panel(frame, toast);
+        draw_text(frame, &lines, toast[0] + PADDING, toast[1] + PADDING, scale);
+    }
+}
+
+/// Black text with its top left at `x`, `y`, each pixel of the font
+/// `scale` pixels wide
+fn draw_text(frame: &mut RgbaImage, lines: &[&str], x: i32, y: i32, scale: i32) {
+    for (row, line) in lines.iter().enumerate() {
+        let top = y + row as i32 * LINE_HEIGHT * scale;
+        for (column, c) in line.chars().enumerate() {
+            let left = x + column as i32 * ADVANCE * scale;
+            for (gy, bits) in glyph(c).into_iter().enumerate() {
+                for gx in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - gx)) != 0 {
+                        let rect = [left + gx * scale, top + gy as i32 * scale, scale, scale];
+                        fill_rect(frame, rect, [0, 0, 0, 255]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a 5x7 glyph, the leftmost pixel in the highest bit. Letters are
+/// all capitals, and anything without a glyph is a box.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00; 7],
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'A' => [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+        '\'' => [0x0c, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        _ => [0x1f, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1f],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::bot::Bot;
+    use crate::events::Subscriber;
+    use crate::headless::Simulation;
+    use crate::settings::Difficulty;
+
+    /// Compare with the image in tests/golden/, or write it when
+    /// `UPDATE_GOLDEN` is set
+    fn assert_golden(frame: &RgbaImage, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            save(frame, &path).unwrap();
+            return;
+        }
+        let golden = image::open(&path).unwrap().to_rgba8();
+        if *frame != golden {
+            let actual = std::env::temp_dir().join(name);
+            save(frame, &actual).unwrap();
+            panic!(
+                "{name} doesn't match {}, it was saved to {}",
+                path.display(),
+                actual.display()
+            );
+        }
+    }
+
+    #[test]
+    fn renders_like_the_golden_image() {
+        let mut sim = Simulation::new(42, Difficulty::Normal);
+        let mut bot = Bot::default();
+        for _ in 0..300 {
+            bot.update(&mut sim.skier.player, &sim.map);
+            for event in sim.step() {
+                bot.on_event(&event);
+            }
+        }
+        let renderer = Renderer::new().unwrap();
+        let frame = renderer.render(sim.assets(), &sim.map, &sim.skier, 480, 640);
+        assert_golden(&frame, "slope_42_tick_300.png");
+    }
+}
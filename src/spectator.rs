@@ -0,0 +1,219 @@
+use ggez::event::EventHandler;
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawParam, PxScale, Rect, Sampler, Text};
+use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::{Context, GameResult};
+
+use crate::assets::Assets;
+use crate::headless::TICKS_PER_SECOND;
+use crate::hud::Hud;
+use crate::map::{wrap_around, wrap_x, Course, Map};
+use crate::net::broadcast::Watch;
+use crate::net::protocol::{Message, SpectatedSkier};
+use crate::player::Player;
+use crate::settings::{Settings, Skin};
+use crate::skier::anchor;
+use crate::util::integer_scale;
+
+/// Pixels per tick the free camera moves
+const PAN_SPEED: f32 = 8.;
+
+/// Shows a game running elsewhere without being able to play it
+pub struct Spectator {
+    assets: Assets,
+    watch: Watch,
+    /// Built from the slope the game is on, once it says which
+    map: Option<Map>,
+    /// Game tick the map has been moved along to
+    map_tick: u32,
+    skiers: Vec<SpectatedSkier>,
+    /// Index of the skier the camera follows, or `None` to move it freely
+    follow: Option<usize>,
+    /// Map position at the skier anchor of the window
+    camera: Vec2,
+    /// Direction the free camera is moving in
+    pan: Vec2,
+    /// Least of the slope to show, scaled up like the game's view
+    view: Vec2,
+}
+
+impl Spectator {
+    pub fn new(ctx: &mut Context, watch: Watch, settings: &Settings) -> GameResult<Self> {
+        Ok(Self {
+            assets: Assets::new(ctx)?,
+            watch,
+            map: None,
+            map_tick: 0,
+            skiers: vec![],
+            follow: Some(0),
+            camera: Player::POSITION.into(),
+            pan: Vec2::ZERO,
+            view: Vec2::new(settings.view_width as f32, settings.view_height as f32),
+        })
+    }
+
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::Welcome {
+                seed, difficulty, ..
+            } => {
+                self.map = Some(Map::new(&self.assets, difficulty, seed));
+                self.map_tick = 0;
+                self.skiers.clear();
+            }
+            Message::Scene { tick, skiers } => {
+                // catch up with objects moving on the map, which takes a
+                // while when joining late in a run
+                if let Some(map) = &mut self.map {
+                    for _ in self.map_tick..tick {
+                        map.update();
+                    }
+                }
+                self.map_tick = self.map_tick.max(tick);
+                self.skiers = skiers;
+            }
+            _ => {}
+        }
+    }
+
+    /// Follow the next (or previous) skier
+    fn cycle(&mut self, step: isize) {
+        let count = self.skiers.len().max(1) as isize;
+        let current = self.follow.unwrap_or(0) as isize;
+        self.follow = Some((current + step).rem_euclid(count) as usize);
+    }
+
+    fn followed(&self) -> Option<&SpectatedSkier> {
+        self.follow.and_then(|i| self.skiers.get(i))
+    }
+
+    fn status(&self) -> String {
+        let mut target = match self.follow {
+            Some(i) => format!("Following player {}", i + 1),
+            None => "Free camera".into(),
+        };
+        if let Some(score) = self.followed().and_then(|s| s.run).map(|run| run.score) {
+            let gates = score.gates_passed + score.gates_missed;
+            if gates > 0 {
+                target += &format!("\nGates: {} of {gates}", score.gates_passed);
+            }
+        }
+        format!("{target}\nTab: next skier  F: free camera")
+    }
+
+    /// The HUD of the skier being followed, as they see it
+    fn hud(&self) -> Option<Hud> {
+        let spectated = self.followed()?;
+        let run = spectated.run?;
+        let position = Vec2::new(spectated.skier.x, spectated.skier.y);
+        let mut hud = Hud::default()
+            .set_distance(run.distance)
+            .set_speed(run.speed)
+            .set_style(run.score.style)
+            .set_position(position, Course::at(position));
+        hud.elapsed_time = run.time;
+        Some(hud)
+    }
+}
+
+impl EventHandler for Spectator {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        while ctx.time.check_update_time(TICKS_PER_SECOND) {
+            let messages = self
+                .watch
+                .poll()
+                .map_err(|e| ggez::GameError::CustomError(format!("{e:#}")))?;
+            for message in messages {
+                self.receive(message);
+            }
+            match self.followed() {
+                Some(spectated) => self.camera = Vec2::new(spectated.skier.x, spectated.skier.y),
+                None => {
+                    self.camera += self.pan * PAN_SPEED;
+                    self.camera.x = wrap_around(self.camera.x);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = Canvas::from_frame(ctx, Color::WHITE);
+        canvas.set_sampler(Sampler::nearest_clamp());
+        // sharp pixels, scaled up by a whole number
+        let window = Vec2::from(ctx.gfx.drawable_size());
+        let scale = integer_scale(window, self.view);
+        let screen = Rect::new(0., 0., window.x / scale, window.y / scale);
+        canvas.set_screen_coordinates(screen);
+        let offset = anchor(screen) - self.camera;
+        let view = Rect::new(-offset.x, -offset.y, screen.w, screen.h);
+
+        if let Some(map) = &mut self.map {
+            map.draw(ctx, &mut canvas, view, offset, self.follow.unwrap_or(0));
+        }
+        for SpectatedSkier { skier, yeti, .. } in &self.skiers {
+            let skin = if skier.id == 0 {
+                Skin::Classic
+            } else {
+                Skin::Ice
+            };
+            let position = Vec2::new(wrap_x(skier.x, self.camera.x), skier.y);
+            canvas.draw(
+                skier.pose.image(&self.assets).as_ref(),
+                DrawParam::default()
+                    .dest(position + offset)
+                    .color(skin.tint()),
+            );
+            if let Some(yeti) = yeti {
+                let position = Vec2::new(wrap_x(yeti.position.x, self.camera.x), yeti.position.y);
+                canvas.draw(
+                    yeti.sprite(&self.assets).as_ref(),
+                    DrawParam::default().dest(position + offset),
+                );
+            }
+        }
+        if let Some(hud) = self.hud() {
+            hud.draw(ctx, &mut canvas, screen);
+        }
+
+        let mut text = Text::new(self.status());
+        text.set_scale(PxScale::from(14.));
+        canvas.draw(&text, DrawParam::new().dest([8., 8.]).color(Color::BLACK));
+
+        canvas.finish(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        input: KeyInput,
+        _repeated: bool,
+    ) -> GameResult {
+        match input.keycode {
+            Some(KeyCode::Tab) => self.cycle(1),
+            Some(KeyCode::Back) => self.cycle(-1),
+            Some(KeyCode::F) => {
+                self.follow = match self.follow {
+                    Some(_) => None,
+                    None => Some(0),
+                };
+            }
+            Some(KeyCode::Left) => self.pan.x = -1.,
+            Some(KeyCode::Right) => self.pan.x = 1.,
+            Some(KeyCode::Up) => self.pan.y = -1.,
+            Some(KeyCode::Down) => self.pan.y = 1.,
+            Some(KeyCode::Escape | KeyCode::Q) => ctx.request_quit(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
+        match input.keycode {
+            Some(KeyCode::Left | KeyCode::Right) => self.pan.x = 0.,
+            Some(KeyCode::Up | KeyCode::Down) => self.pan.y = 0.,
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! What happened during a run, shown when it's over and added up across
+//! sessions
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use ggez::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{load_toml, save_toml};
+use crate::events::{GameEvent, Subscriber};
+use crate::headless::TICKS_PER_SECOND;
+use crate::map::Progress;
+use crate::player::{CollisionAction, Player};
+use crate::race::format_time;
+
+/// Cause of falls that weren't into anything
+const WIPEOUT: &str = "wipeout";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStats {
+    pub jumps: u32,
+    pub tricks_attempted: u32,
+    pub tricks_landed: u32,
+    /// Pixels a tick
+    pub top_speed: f32,
+    /// Most ticks skied upright between falls
+    pub longest_streak: u32,
+    // tables last, as TOML wants
+    /// Ticks skied on each course, by `Course::id`
+    pub course_ticks: BTreeMap<String, u32>,
+    /// Falls by what caused them, an `ObjectKind::name` or a wipeout
+    /// landing a trick
+    pub falls: BTreeMap<String, u32>,
+    #[serde(skip)]
+    streak: u32,
+    /// What the skier just ran into, until they fall
+    #[serde(skip)]
+    hit: Option<&'static str>,
+}
+
+impl RunStats {
+    /// Keep track of the skier after a tick
+    pub fn update(&mut self, player: &Player, progress: &Progress) {
+        if player.is_eaten() || progress.is_finished() {
+            return;
+        }
+        if let Some(course) = progress.course() {
+            *self.course_ticks.entry(course.id().into()).or_default() += 1;
+        }
+        self.top_speed = self.top_speed.max(player.current_speed());
+        if player.is_upright() {
+            self.streak += 1;
+            self.longest_streak = self.longest_streak.max(self.streak);
+        }
+    }
+
+    pub fn fall_count(&self) -> u32 {
+        self.falls.values().sum()
+    }
+
+    /// Add another run, e.g. to the totals
+    pub fn add(&mut self, run: &RunStats) {
+        for (course, ticks) in &run.course_ticks {
+            *self.course_ticks.entry(course.clone()).or_default() += ticks;
+        }
+        for (cause, falls) in &run.falls {
+            *self.falls.entry(cause.clone()).or_default() += falls;
+        }
+        self.jumps += run.jumps;
+        self.tricks_attempted += run.tricks_attempted;
+        self.tricks_landed += run.tricks_landed;
+        self.top_speed = self.top_speed.max(run.top_speed);
+        self.longest_streak = self.longest_streak.max(run.longest_streak);
+    }
+
+    /// The breakdown, a line for each stat
+    pub fn lines(&self) -> Vec<String> {
+        let seconds = |ticks: u32| format_time(ticks as f32 / TICKS_PER_SECOND as f32);
+        let mut lines: Vec<_> = self
+            .course_ticks
+            .iter()
+            .map(|(course, ticks)| format!("{}: {}", course.replace('_', " "), seconds(*ticks)))
+            .collect();
+        let causes: Vec<_> = self
+            .falls
+            .iter()
+            .map(|(cause, falls)| format!("{} {falls}", cause.replace('_', " ")))
+            .collect();
+        lines.push(if causes.is_empty() {
+            "Falls: 0".into()
+        } else {
+            format!("Falls: {} ({})", self.fall_count(), causes.join(", "))
+        });
+        lines.push(format!("Jumps: {}", self.jumps));
+        lines.push(format!(
+            "Tricks landed: {} of {}",
+            self.tricks_landed, self.tricks_attempted
+        ));
+        lines.push(format!(
+            "Top speed: {:.0}px/s",
+            self.top_speed * TICKS_PER_SECOND as f32
+        ));
+        lines.push(format!(
+            "Longest without a fall: {}",
+            seconds(self.longest_streak)
+        ));
+        lines
+    }
+}
+
+impl Subscriber for RunStats {
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Collided(kind, CollisionAction::Fall) => self.hit = Some(kind.name()),
+            GameEvent::Fell => {
+                let cause = self.hit.take().unwrap_or(WIPEOUT);
+                *self.falls.entry(cause.into()).or_default() += 1;
+                self.streak = 0;
+            }
+            GameEvent::Jumped { .. } => self.jumps += 1,
+            GameEvent::TrickStarted(_) => self.tricks_attempted += 1,
+            GameEvent::TrickSucceeded(_) => self.tricks_landed += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Every run added up, stored as TOML next to the config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Totals {
+    pub runs: u32,
+    pub stats: RunStats,
+}
+
+impl Totals {
+    const FILE_NAME: &'static str = "stats.toml";
+
+    pub fn path(ctx: &Context) -> PathBuf {
+        ctx.fs.user_config_dir().join(Self::FILE_NAME)
+    }
+
+    /// Totals saved at `path`, or zero runs before the first is saved
+    pub fn load(path: &Path) -> Result<Self> {
+        load_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        save_toml(self, path)
+    }
+
+    pub fn add(&mut self, run: &RunStats) {
+        self.runs += 1;
+        self.stats.add(run);
+    }
+
+    /// The end of run screen: the run, then every run so far
+    pub fn summary(&self, run: &RunStats) -> String {
+        let mut lines = vec!["This run".to_string()];
+        lines.extend(run.lines());
+        lines.push(String::new());
+        lines.push(format!("All {} runs", self.runs));
+        lines.extend(self.stats.lines());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Simulation;
+    use crate::map::objects::ObjectKind;
+    use crate::map::Course;
+    use crate::player::TrickType;
+    use crate::settings::Difficulty;
+
+    #[test]
+    fn counts_falls_by_cause() {
+        let mut run = RunStats::default();
+        for event in [
+            GameEvent::Collided(ObjectKind::Tree, CollisionAction::Fall),
+            GameEvent::Fell,
+            GameEvent::Collided(ObjectKind::Ramp, CollisionAction::JumpLarge),
+            GameEvent::Jumped { large: true },
+            GameEvent::TrickStarted(TrickType::Flip),
+            GameEvent::TrickFailed(TrickType::Flip),
+            GameEvent::Fell,
+            GameEvent::TrickStarted(TrickType::Trick1),
+            GameEvent::TrickSucceeded(TrickType::Trick1),
+            GameEvent::Collided(ObjectKind::Tree, CollisionAction::Fall),
+            GameEvent::Fell,
+        ] {
+            run.on_event(&event);
+        }
+        assert_eq!(run.falls["tree"], 2);
+        assert_eq!(run.falls[WIPEOUT], 1);
+        assert_eq!(
+            (run.jumps, run.tricks_attempted, run.tricks_landed),
+            (1, 2, 1)
+        );
+        assert!(run
+            .lines()
+            .contains(&"Falls: 3 (tree 2, wipeout 1)".to_string()));
+
+        let mut totals = Totals::default();
+        totals.add(&run);
+        totals.add(&run);
+        let toml = toml::to_string_pretty(&totals).unwrap();
+        let loaded: Totals = toml::from_str(&toml).unwrap();
+        assert_eq!(loaded, totals);
+        assert_eq!(loaded.runs, 2);
+        assert_eq!(loaded.stats.fall_count(), 6);
+    }
+
+    #[test]
+    fn times_courses_and_streaks() {
+        let mut sim = Simulation::new(1, Difficulty::Easy);
+        sim.skier.player.place(Course::TreeSlalom.start());
+        sim.skier.player.steer(Some(0.));
+        // straight down through the trees until one is hit
+        let mut upright = 0;
+        while sim.tick() < 3000 && sim.skier.stats.fall_count() == 0 {
+            sim.step();
+            upright += sim.skier.player.is_upright() as u32;
+        }
+        let stats = &sim.skier.stats;
+        assert_eq!(stats.fall_count(), 1);
+        assert!(!sim.skier.player.is_upright());
+        assert_eq!(stats.longest_streak, upright);
+        assert!(stats.top_speed > 0.);
+        assert!(stats.course_ticks["tree_slalom"] > 0);
+    }
+}
@@ -0,0 +1,287 @@
+//! What a skier did every tick, saved as CSV or JSON Lines for working out
+//! where players struggle
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{bail, Context as _, Result};
+use ggez::glam::Vec2;
+use serde::Serialize;
+
+use crate::events::GameEvent;
+use crate::map::{wrap_x, Map};
+use crate::player::{CollisionAction, Player};
+
+/// Obstacles logged each tick, nearest first
+pub const NEAREST: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    /// The format a path's extension asks for
+    pub fn of(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            Some("jsonl") => Ok(Format::JsonLines),
+            _ => bail!(
+                "telemetry is saved as .csv or .jsonl, not {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// An object the skier could run into and where it is from them, between
+/// the middles of their hitboxes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Obstacle {
+    pub kind: &'static str,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Collision {
+    pub kind: &'static str,
+    pub action: &'static str,
+}
+
+/// One skier after one tick
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    /// Counting from 0. Restarting starts the next run on the same log.
+    pub run: u32,
+    pub tick: u32,
+    pub skier: usize,
+    pub state: &'static str,
+    /// What the snow is like underfoot
+    pub surface: &'static str,
+    /// Angle skied in, in radians with 0 straight down. None when stopped.
+    pub direction: Option<f32>,
+    /// Pixels a tick
+    pub speed: f32,
+    /// Distance down the slope
+    pub distance: f32,
+    pub x: f32,
+    pub y: f32,
+    pub nearest: Vec<Obstacle>,
+    pub collision: Option<Collision>,
+}
+
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    samples: Vec<Sample>,
+    run: u32,
+}
+
+impl Telemetry {
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Log the ticks from now on as the next run, unless nothing of this one
+    /// was logged yet
+    pub fn next_run(&mut self) {
+        if self.samples.last().is_some_and(|s| s.run == self.run) {
+            self.run += 1;
+        }
+    }
+
+    /// Log `player`, skier number `skier`, after `tick` and the events it
+    /// brought
+    pub fn record(
+        &mut self,
+        tick: u32,
+        skier: usize,
+        player: &Player,
+        map: &Map,
+        events: &[GameEvent],
+    ) {
+        let feet = player.hitbox().center();
+        let mut nearest: Vec<_> = map
+            .objects()
+            .iter()
+            .filter(|o| o.collision_action != CollisionAction::Nothing)
+            .map(|o| {
+                let center = o.hitbox().center();
+                let offset = Vec2::new(wrap_x(center.x, feet.x) - feet.x, center.y - feet.y);
+                (o.kind, offset)
+            })
+            .collect();
+        let by_distance =
+            |a: &(_, Vec2), b: &(_, Vec2)| a.1.length_squared().total_cmp(&b.1.length_squared());
+        if nearest.len() > NEAREST {
+            nearest.select_nth_unstable_by(NEAREST, by_distance);
+            nearest.truncate(NEAREST);
+        }
+        nearest.sort_by(by_distance);
+
+        let collision = events.iter().find_map(|e| match e {
+            GameEvent::Collided(kind, action) => Some(Collision {
+                kind: kind.name(),
+                action: action.name(),
+            }),
+            _ => None,
+        });
+        let position = player.position();
+        self.samples.push(Sample {
+            run: self.run,
+            tick,
+            skier,
+            state: player.state_name(),
+            surface: player.surface().name(),
+            direction: player.direction(),
+            speed: player.current_speed(),
+            distance: player.distance(),
+            x: position.x,
+            y: position.y,
+            nearest: nearest
+                .into_iter()
+                .map(|(kind, offset)| Obstacle {
+                    kind: kind.name(),
+                    dx: offset.x,
+                    dy: offset.y,
+                })
+                .collect(),
+            collision,
+        });
+    }
+
+    /// Write everything logged to `path`, in the format its extension asks
+    /// for
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = match Format::of(path)? {
+            Format::Csv => self.to_csv(),
+            Format::JsonLines => self.to_json_lines()?,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+    }
+
+    fn to_json_lines(&self) -> Result<String> {
+        let mut lines = String::new();
+        for sample in &self.samples {
+            lines += &serde_json::to_string(sample)?;
+            lines.push('\n');
+        }
+        Ok(lines)
+    }
+
+    /// One row a sample. Columns of obstacles and collisions that aren't
+    /// there are left empty.
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("run,tick,skier,state,surface,direction,speed,distance,x,y");
+        for i in 1..=NEAREST {
+            write!(csv, ",obstacle{i},obstacle{i}_dx,obstacle{i}_dy").unwrap();
+        }
+        csv += ",collision,collision_action\n";
+
+        for s in &self.samples {
+            let direction = s.direction.map_or(String::new(), |d| format!("{d:.3}"));
+            write!(
+                csv,
+                "{},{},{},{},{},{direction},{:.2},{:.2},{:.2},{:.2}",
+                s.run, s.tick, s.skier, s.state, s.surface, s.speed, s.distance, s.x, s.y
+            )
+            .unwrap();
+            for i in 0..NEAREST {
+                match s.nearest.get(i) {
+                    Some(o) => write!(csv, ",{},{:.2},{:.2}", o.kind, o.dx, o.dy).unwrap(),
+                    None => csv += ",,,",
+                }
+            }
+            match s.collision {
+                Some(c) => writeln!(csv, ",{},{}", c.kind, c.action).unwrap(),
+                None => csv += ",,\n",
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Simulation;
+    use crate::map::Course;
+    use crate::settings::Difficulty;
+
+    #[test]
+    fn logs_every_tick_and_collision() {
+        let mut sim = Simulation::new(9, Difficulty::Hard);
+        sim.skier.player.place(Course::TreeSlalom.start());
+        sim.skier.player.steer(Some(0.));
+        let mut telemetry = Telemetry::default();
+        // straight down through the trees until one is hit
+        while sim.tick() < 3000 && telemetry.samples().iter().all(|s| s.collision.is_none()) {
+            let events = sim.step();
+            telemetry.record(sim.tick(), 0, &sim.skier.player, &sim.map, &events);
+        }
+        let samples = telemetry.samples();
+        let last = samples.last().unwrap();
+        assert!(last.collision.is_some(), "hit something");
+        assert_eq!(samples.len() as u32, sim.tick());
+        assert_eq!(last.nearest.len(), NEAREST);
+        let distances: Vec<_> = last.nearest.iter().map(|o| o.dx.hypot(o.dy)).collect();
+        assert!(distances.windows(2).all(|d| d[0] <= d[1]), "nearest first");
+
+        let dir = std::env::temp_dir().join(format!("skifree-telemetry-{}", std::process::id()));
+        telemetry.save(&dir.join("run.csv")).unwrap();
+        telemetry.save(&dir.join("run.jsonl")).unwrap();
+        let csv = std::fs::read_to_string(dir.join("run.csv")).unwrap();
+        let rows: Vec<_> = csv.lines().collect();
+        assert_eq!(rows.len(), samples.len() + 1);
+        assert_eq!(rows[0].split(',').count(), rows[1].split(',').count());
+        let collision = rows.last().unwrap().rsplit(',').next().unwrap();
+        assert!([
+            "fall",
+            "glance",
+            "stumble",
+            "deflect",
+            "jump_small",
+            "jump_large",
+            "ride"
+        ]
+        .contains(&collision));
+
+        let jsonl = std::fs::read_to_string(dir.join("run.jsonl")).unwrap();
+        let last_line: serde_json::Value =
+            serde_json::from_str(jsonl.lines().last().unwrap()).unwrap();
+        assert_eq!(last_line["tick"], sim.tick());
+        assert_eq!(last_line["state"], last.state);
+        assert_eq!(last_line["run"], 0);
+        assert!(telemetry.save(&dir.join("run.txt")).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restarts_log_the_next_run() {
+        let mut sim = Simulation::new(9, Difficulty::Normal);
+        let mut telemetry = Telemetry::default();
+        telemetry.next_run();
+        for _ in 0..2 {
+            for _ in 0..10 {
+                let events = sim.step();
+                telemetry.record(sim.tick(), 0, &sim.skier.player, &sim.map, &events);
+            }
+            telemetry.next_run();
+            telemetry.next_run();
+            sim = Simulation::new(9, Difficulty::Normal);
+        }
+        let runs: Vec<_> = telemetry
+            .samples()
+            .iter()
+            .map(|s| (s.run, s.tick))
+            .collect();
+        assert_eq!(runs.len(), 20);
+        assert_eq!(runs[9], (0, 10));
+        assert_eq!(runs[10], (1, 1));
+        assert_eq!(runs[19], (1, 10));
+    }
+}
@@ -18,6 +18,20 @@ pub fn angle_from_vec2(vec2: Vec2) -> f32 {
     vec2.x.atan2(vec2.y)
 }
 
+/// Biggest whole number the view can be scaled up by and still fit in the
+/// window, at least 1
+pub fn integer_scale(window: Vec2, view: Vec2) -> f32 {
+    (window / view).min_element().floor().max(1.)
+}
+
+/// Size of what's being drawn on the canvas, in the canvas's coordinates
+pub fn screen_size(ctx: &Context, canvas: &Canvas) -> Vec2 {
+    canvas.screen_coordinates().map_or_else(
+        || ctx.gfx.drawable_size().into(),
+        |screen| screen.size().into(),
+    )
+}
+
 pub fn draw_hitbox(ctx: &Context, canvas: &mut Canvas, hitbox: Rect) {
     canvas.draw(
         &Mesh::new_rectangle(ctx, DrawMode::stroke(1.), hitbox, Color::RED).unwrap(),
@@ -39,6 +53,16 @@ mod tests {
         assert_eq!(vec2_from_angle(3. * FRAC_PI_2).as_ivec2(), [-1, 0].into());
     }
 
+    #[test]
+    fn scales_by_whole_numbers() {
+        let view = Vec2::new(480., 640.);
+        assert_eq!(integer_scale(view, view), 1.);
+        assert_eq!(integer_scale(Vec2::new(1920., 1080.), view), 1.);
+        assert_eq!(integer_scale(Vec2::new(1440., 1300.), view), 2.);
+        assert_eq!(integer_scale(Vec2::new(2560., 2000.), view), 3.);
+        assert_eq!(integer_scale(Vec2::new(200., 200.), view), 1.);
+    }
+
     #[test]
     fn can_convert_vec2_to_angle() {
         assert_eq!(angle_from_vec2([0., 1.].into()), 0.);
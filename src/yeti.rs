@@ -0,0 +1,137 @@
+use std::rc::Rc;
+
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, DrawParam, Rect};
+use ggez::Context;
+
+use crate::animation::{YETI_EAT, YETI_RUN};
+use crate::assets::{Assets, Sprite};
+use crate::events::GameEvent;
+use crate::map::wrap_x;
+use crate::player::Player;
+use crate::util::draw_hitbox;
+
+/// Distance down the mountain where the yeti starts chasing the skier
+const APPEAR_DISTANCE: f32 = 2000.;
+/// A little faster than the skier, but slower than a boosting one
+const SPEED: f32 = 3.6;
+/// How far behind the skier the yeti gives up
+const ESCAPE_DISTANCE: f32 = 1200.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum YetiState {
+    Waiting,
+    Chasing,
+    /// Frames spent eating so far
+    Eating(u32),
+    Gone,
+}
+
+/// The yeti as it's drawn, e.g. for spectators elsewhere
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YetiSighting {
+    /// Position on the map
+    pub position: Vec2,
+    pub eating: bool,
+    /// Ticks into its animation
+    pub tick: u32,
+}
+
+impl YetiSighting {
+    pub fn sprite<'a>(&self, assets: &'a Assets) -> &'a Rc<Sprite> {
+        let clip = if self.eating { &YETI_EAT } else { &YETI_RUN };
+        clip.frame_at(self.tick)(assets)
+    }
+}
+
+/// The abominable snowman, who chases down skiers that go too far
+#[derive(Debug)]
+pub struct Yeti {
+    state: YetiState,
+    /// Position on the map
+    position: Vec2,
+    frame: u32,
+}
+
+impl Default for Yeti {
+    fn default() -> Self {
+        Self {
+            state: YetiState::Waiting,
+            position: Vec2::ZERO,
+            frame: 0,
+        }
+    }
+}
+
+impl Yeti {
+    pub fn update(&mut self, player: &mut Player) {
+        self.frame += 1;
+        match self.state {
+            YetiState::Waiting if player.distance() >= APPEAR_DISTANCE => {
+                // come in from above the top of the screen
+                self.position = player.position() + Vec2::new(-100., -260.);
+                self.state = YetiState::Chasing;
+                player.events().emit(GameEvent::YetiAppeared);
+            }
+            YetiState::Chasing => {
+                let target = player.position();
+                let target = Vec2::new(wrap_x(target.x, self.position.x), target.y);
+                let offset = target - self.position;
+                if offset.length() > ESCAPE_DISTANCE {
+                    self.state = YetiState::Gone;
+                    player.events().emit(GameEvent::YetiEscaped);
+                } else if self.hitbox().overlaps(&player.hitbox()) && !player.is_riding() {
+                    self.state = YetiState::Eating(0);
+                    player.eaten();
+                    player.events().emit(GameEvent::YetiCaught);
+                } else {
+                    self.position += offset.normalize_or_zero() * SPEED;
+                }
+            }
+            YetiState::Eating(frames) => self.state = YetiState::Eating(frames + 1),
+            YetiState::Waiting | YetiState::Gone => {}
+        }
+    }
+
+    /// What there is to see of the yeti, once it has shown up
+    pub fn sighting(&self) -> Option<YetiSighting> {
+        let (eating, tick) = match self.state {
+            YetiState::Waiting | YetiState::Gone => return None,
+            YetiState::Eating(frames) => (true, frames),
+            YetiState::Chasing => (false, self.frame),
+        };
+        Some(YetiSighting {
+            position: self.position,
+            eating,
+            tick,
+        })
+    }
+
+    /// Sprite to draw and where, once the yeti has shown up
+    pub fn sprite<'a>(&self, assets: &'a Assets) -> Option<(&'a Rc<Sprite>, Vec2)> {
+        self.sighting()
+            .map(|sighting| (sighting.sprite(assets), sighting.position))
+    }
+
+    fn hitbox(&self) -> Rect {
+        // the same size as the skier's, at the yeti's feet
+        Rect::new(self.position.x, self.position.y + 38., 25., 5.)
+    }
+
+    /// Draw the yeti. `offset` moves map coordinates to screen coordinates.
+    pub fn draw(&self, ctx: &Context, assets: &Assets, canvas: &mut Canvas, offset: Vec2) {
+        let Some((sprite, position)) = self.sprite(assets) else {
+            return;
+        };
+        #[cfg(debug_assertions)]
+        {
+            let mut hitbox = self.hitbox();
+            hitbox.translate(offset);
+            draw_hitbox(ctx, canvas, hitbox);
+        }
+        canvas.draw(
+            sprite.as_ref(),
+            DrawParam::default().dest(position + offset),
+        );
+    }
+}